@@ -0,0 +1,158 @@
+// checks every password of the vault for the usual problems, nothing in here changes entries
+use std::collections::HashMap;
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+use crate::Password;
+use crate::strength;
+
+const DAY: i64 = 24 * 60 * 60;
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AuditSettings {
+    // passwords not changed for this many days are reported as old, 0 turns it off
+    pub max_age_days: u64,
+    // a local copy of the Have I Been Pwned password list, see hibp.rs
+    pub breach_list: Option<PathBuf>
+}
+
+impl Default for AuditSettings {
+
+    fn default() -> AuditSettings {
+        AuditSettings {
+            max_age_days: 365,
+            breach_list: None
+        }
+    }
+
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Issue {
+    // how often it shows up in the breach list
+    Breached { count: u64 },
+    Weak { strength: &'static str, bits: u32 },
+    // titles of the other entries with the same password
+    Reused { with: Vec<String> },
+    // titles of the other entries with nearly the same password
+    Similar { with: Vec<String> },
+    Old { days: i64 }
+}
+
+impl Issue {
+
+    pub fn describe(&self) -> String {
+        match self {
+            Issue::Breached { count } => format!("found {} times in breaches", count),
+            Issue::Weak { strength, bits } => format!("{} ({} bits)", strength, bits),
+            Issue::Reused { with } => format!("reused in {}", with.join(", ")),
+            Issue::Similar { with } => format!("similar to {}", with.join(", ")),
+            Issue::Old { days } => format!("not changed in {} days", days)
+        }
+    }
+
+}
+
+#[derive(Serialize)]
+pub struct Finding {
+    pub id: usize,
+    pub title: String,
+    pub username: String,
+    pub issues: Vec<Issue>
+}
+
+/// The entries with at least one issue, the ones with the most issues first. Entries
+/// without a password, like notes, aren't looked at. `breaches` are the results of
+/// `hibp::check`, by entry id.
+pub fn audit(passwords: &[Password], settings: &AuditSettings, breaches: &HashMap<usize, u64>, now: i64) -> Vec<Finding> {
+    let audited: Vec<&Password> = passwords.iter().filter(|p| !p.password.is_empty()).collect();
+    let mut by_password: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, password) in audited.iter().enumerate() {
+        by_password.entry(password.password.expose()).or_default().push(i);
+    }
+    let prepared: Vec<Prepared> = audited.iter().map(|password| Prepared::new(password.password.expose())).collect();
+    let titles = |indexes: &mut dyn Iterator<Item = usize>| indexes.map(|i| audited[i].title.to_owned()).collect::<Vec<String>>();
+
+    let mut findings = vec![];
+    for (i, password) in audited.iter().enumerate() {
+        let secret = password.password.expose();
+        let mut issues = vec![];
+        if let Some(count) = breaches.get(&password.id) {
+            issues.push(Issue::Breached { count: *count });
+        }
+        let estimate = strength::estimate(secret);
+        if estimate.is_weak() {
+            issues.push(Issue::Weak { strength: estimate.strength.label(), bits: estimate.bits as u32 });
+        }
+        let same = &by_password[secret];
+        if same.len() > 1 {
+            issues.push(Issue::Reused { with: titles(&mut same.iter().copied().filter(|other| *other != i)) });
+        }
+        let mut similar = (0..audited.len())
+            .filter(|other| *other != i && audited[*other].password.expose() != secret)
+            .filter(|other| prepared[i].is_similar(&prepared[*other]));
+        let similar = titles(&mut similar);
+        if !similar.is_empty() {
+            issues.push(Issue::Similar { with: similar });
+        }
+        let days = (now - password.modified_at) / DAY;
+        if settings.max_age_days > 0 && days >= settings.max_age_days as i64 {
+            issues.push(Issue::Old { days });
+        }
+
+        if !issues.is_empty() {
+            findings.push(Finding {
+                id: password.id,
+                title: password.title.to_owned(),
+                username: password.username.to_owned(),
+                issues
+            });
+        }
+    }
+    // stable, so entries with as many issues stay in vault order
+    findings.sort_by_key(|finding| std::cmp::Reverse(finding.issues.len()));
+    findings
+}
+
+// a password taken apart for comparing it with the others, so that's done once per entry
+struct Prepared {
+    // lowercase, without digits and symbols at either end
+    base: Zeroizing<String>,
+    chars: Zeroizing<Vec<char>>
+}
+
+impl Prepared {
+
+    fn new(password: &str) -> Prepared {
+        let lowercase = Zeroizing::new(password.to_lowercase());
+        let base = lowercase.trim_matches(|c: char| c.is_ascii_digit() || c.is_ascii_punctuation()).to_owned();
+        Prepared { base: Zeroizing::new(base), chars: Zeroizing::new(password.chars().collect()) }
+    }
+
+    // the same password with another number or symbol tacked on, like Summer2023! and
+    // Summer2024!, or one that's a typo or two away
+    fn is_similar(&self, other: &Prepared) -> bool {
+        if self.base.chars().count() >= 4 && self.base == other.base {
+            return true;
+        }
+        let (a, b) = (&self.chars, &other.chars);
+        a.len().min(b.len()) >= 8 && a.len().abs_diff(b.len()) <= 2 && edit_distance(a, b) <= 2
+    }
+
+}
+
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
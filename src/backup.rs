@@ -0,0 +1,143 @@
+// rotating copies of a vault, kept in a directory next to it
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use chrono::{Datelike, Local, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use crate::db::{Database, DbError};
+
+// local time, which is what a day or week is counted in
+const TIME_FORMAT: &str = "%Y%m%d-%H%M%S";
+const TIME_LENGTH: usize = 15;
+const EXTENSION: &str = "bak";
+
+/// How many backups are kept. A backup stays as long as any of the rules wants it, and
+/// all of them at 0 turns backups off.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BackupSettings {
+    // the most recent ones
+    pub keep_last: usize,
+    // the newest one of each of this many days
+    pub keep_daily: usize,
+    // the newest one of each of this many weeks
+    pub keep_weekly: usize
+}
+
+impl Default for BackupSettings {
+
+    fn default() -> BackupSettings {
+        BackupSettings {
+            keep_last: 10,
+            keep_daily: 7,
+            keep_weekly: 4
+        }
+    }
+
+}
+
+impl BackupSettings {
+
+    pub fn enabled(&self) -> bool {
+        self.keep_last + self.keep_daily + self.keep_weekly > 0
+    }
+
+}
+
+pub struct Backup {
+    pub path: PathBuf,
+    pub created: NaiveDateTime
+}
+
+impl Backup {
+
+    pub fn name(&self) -> String {
+        self.path.file_name().unwrap().to_string_lossy().into_owned()
+    }
+
+}
+
+/// The directory the backups of `vault` go to, `my_passmng` gets `my_passmng.backups`.
+pub fn dir(vault: &Path) -> PathBuf {
+    let mut dir = OsString::from(vault);
+    dir.push(".backups");
+    PathBuf::from(dir)
+}
+
+/// The backups of `vault`, newest first.
+pub fn list(vault: &Path) -> Result<Vec<Backup>, std::io::Error> {
+    let dir = dir(vault);
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut backups = vec![];
+    for file in std::fs::read_dir(dir)? {
+        let path = file?.path();
+        if path.extension().map(|ext| ext != EXTENSION).unwrap_or(true) {
+            continue;
+        }
+        // names are `<vault>-<time>.bak`, anything else in there isn't ours
+        let stem = path.file_stem().unwrap().to_string_lossy().into_owned();
+        let created = stem.len().checked_sub(TIME_LENGTH)
+            .and_then(|start| stem.get(start..))
+            .and_then(|time| NaiveDateTime::parse_from_str(time, TIME_FORMAT).ok());
+        if let Some(created) = created {
+            backups.push(Backup { path, created });
+        }
+    }
+    backups.sort_by_key(|backup| std::cmp::Reverse(backup.created));
+    Ok(backups)
+}
+
+/// Takes a backup of the open vault and thins out the old ones.
+pub fn create(db: &Database, settings: &BackupSettings) -> Result<PathBuf, DbError> {
+    let dir = dir(db.path());
+    std::fs::create_dir_all(&dir)?;
+    let name = db.path().file_name().unwrap().to_string_lossy().into_owned();
+    let path = dir.join(format!("{}-{}.{}", name, Local::now().format(TIME_FORMAT), EXTENSION));
+    // one a second is plenty
+    if !path.exists() {
+        db.backup_to(&path)?;
+    }
+    // with backups turned off this is only called before a restore, and that copy stays
+    if settings.enabled() {
+        prune(db.path(), settings)?;
+    }
+    Ok(path)
+}
+
+fn prune(vault: &Path, settings: &BackupSettings) -> Result<(), std::io::Error> {
+    let backups = list(vault)?;
+    let mut keep: HashSet<usize> = (0..settings.keep_last.min(backups.len())).collect();
+    // backups are newest first, so the first one seen of a day or week is the one to keep
+    let mut days = vec![];
+    let mut weeks = vec![];
+    for (i, backup) in backups.iter().enumerate() {
+        let day = backup.created.date();
+        if !days.contains(&day) && days.len() < settings.keep_daily {
+            days.push(day);
+            keep.insert(i);
+        }
+        let week = day.iso_week();
+        if !weeks.contains(&week) && weeks.len() < settings.keep_weekly {
+            weeks.push(week);
+            keep.insert(i);
+        }
+    }
+    for (i, backup) in backups.iter().enumerate() {
+        if !keep.contains(&i) {
+            std::fs::remove_file(&backup.path)?;
+            let kdf_file = crate::db::kdf_file(&backup.path);
+            if kdf_file.exists() {
+                std::fs::remove_file(kdf_file)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Finds a backup of `vault` by its file name, or takes `name` as a path.
+pub fn find(vault: &Path, name: &str) -> Result<PathBuf, std::io::Error> {
+    let found = list(vault)?.into_iter().find(|backup| backup.name() == name);
+    Ok(found.map(|backup| backup.path).unwrap_or_else(|| PathBuf::from(name)))
+}
@@ -0,0 +1,657 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use clap::{Args, Parser, Subcommand};
+use crate::config::Config;
+use crate::db::{Database, DbError};
+use crate::export::Format as ExportFormat;
+use crate::folders::Folder;
+use crate::generator::GeneratorMode;
+use crate::import::{Format, Imported};
+use crate::search::Query;
+use crate::secret::SecretString;
+use crate::totp::Totp;
+use crate::{CustomField, Password};
+
+#[derive(Parser)]
+#[command(name = "passmng", version, about = "A simple TUI Password Manager")]
+pub struct Cli {
+    /// Vault to open, either the name of a vault profile or a path to a vault file
+    #[arg(long, global = true)]
+    pub vault: Option<String>,
+    #[command(subcommand)]
+    pub command: Option<Command>
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Print the password (or username) of an entry
+    Get {
+        title: String,
+        /// Output the username instead of the password
+        #[arg(short, long)]
+        username: bool,
+        /// Output the custom field with this name instead of the password
+        #[arg(short, long, conflicts_with = "username")]
+        field: Option<String>,
+        /// Output the current TOTP code instead of the password
+        #[arg(short, long, conflicts_with_all = ["username", "field"])]
+        totp: bool,
+        /// Copy to the clipboard instead of printing
+        #[arg(short, long)]
+        copy: bool
+    },
+    /// Add a new entry. Password is asked for if not given
+    Add {
+        title: String,
+        username: String,
+        #[arg(short, long)]
+        password: Option<String>,
+        #[command(flatten)]
+        fields: EntryFields
+    },
+    /// Edit the fields of an existing entry
+    Edit {
+        title: String,
+        #[arg(long = "title")]
+        new_title: Option<String>,
+        #[arg(short, long)]
+        username: Option<String>,
+        #[arg(short, long)]
+        password: Option<String>,
+        #[command(flatten)]
+        fields: EntryFields
+    },
+    /// Print every field of an entry except the password
+    Show {
+        title: String
+    },
+    /// Print the earlier passwords of an entry, the most recent first, with when they were replaced
+    History {
+        title: String
+    },
+    /// Move an entry to a folder, given by its path like Work/Servers, or to the top level
+    Mv {
+        title: String,
+        folder: Option<String>
+    },
+    /// Move an entry to the trash
+    Rm {
+        title: String
+    },
+    /// List all entries
+    Ls,
+    /// List entries matching the query, best match first. Terms can be limited to a field
+    /// with title:, user:, url:, tag: or notes:
+    Search {
+        query: String
+    },
+    /// Report weak, reused, similar and old passwords
+    Audit {
+        /// Print the report as JSON, passwords themselves are never included
+        #[arg(long)]
+        json: bool,
+        /// Have I Been Pwned password list to check against, instead of the one in passmng.toml
+        #[arg(long)]
+        breach_list: Option<PathBuf>
+    },
+    /// Import entries from the export of another password manager
+    Import {
+        #[arg(value_enum)]
+        format: Format,
+        file: PathBuf,
+        /// Don't ask before importing
+        #[arg(short, long)]
+        yes: bool,
+        /// Also import entries that look like ones already in the vault
+        #[arg(long)]
+        keep_duplicates: bool,
+        /// Only show what would be imported
+        #[arg(long)]
+        dry_run: bool
+    },
+    /// Write every entry to a file, either plaintext or encrypted with a passphrase of its own
+    Export {
+        #[arg(value_enum)]
+        format: ExportFormat,
+        file: PathBuf,
+        /// Don't ask before writing a plaintext export
+        #[arg(short, long)]
+        yes: bool
+    },
+    /// Create the vault. Other commands never create one, so a mistyped vault is an error
+    Init,
+    /// Change the passphrase of the vault, a backup of the old file is kept
+    ChangePassphrase,
+    /// Generate a password. Settings not given come from passmng.toml
+    Generate(GenerateArgs),
+    /// List or restore the automatic backups of the vault
+    Backup {
+        #[command(subcommand)]
+        action: BackupAction
+    },
+    /// Manage the folders entries are sorted into
+    Folder {
+        #[command(subcommand)]
+        action: FolderAction
+    },
+    /// List, restore or purge deleted entries
+    Trash {
+        #[command(subcommand)]
+        action: TrashAction
+    },
+    /// Manage named vault profiles
+    Vault {
+        #[command(subcommand)]
+        action: VaultAction
+    }
+}
+
+#[derive(Args)]
+pub struct GenerateArgs {
+    /// Number of characters
+    #[arg(short, long)]
+    length: Option<usize>,
+    /// Generate a passphrase of this many words instead
+    #[arg(short, long)]
+    words: Option<usize>,
+    /// Separator between the words of a passphrase
+    #[arg(long)]
+    separator: Option<String>,
+    #[arg(long)]
+    no_lowercase: bool,
+    #[arg(long)]
+    no_uppercase: bool,
+    #[arg(long)]
+    no_digits: bool,
+    #[arg(long)]
+    no_symbols: bool,
+    /// Leave out characters that are easy to mix up, like l, 1 and I
+    #[arg(long)]
+    exclude_ambiguous: bool,
+    /// Don't require a character of every enabled class
+    #[arg(long)]
+    no_must_include: bool
+}
+
+#[derive(Args)]
+pub struct EntryFields {
+    /// URL of the entry, can be repeated. On edit, replaces all URLs
+    #[arg(long = "url")]
+    urls: Vec<String>,
+    /// Tag of the entry, can be repeated. On edit, replaces all tags
+    #[arg(long = "tag")]
+    tags: Vec<String>,
+    #[arg(long)]
+    notes: Option<String>,
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    favorite: Option<bool>,
+    /// Custom field as NAME=VALUE, can be repeated. Replaces a field with the same name
+    #[arg(long = "field", value_parser = parse_custom_field)]
+    fields: Vec<(String, String)>,
+    /// Like --field, but the field is hidden
+    #[arg(long = "secret", value_parser = parse_custom_field)]
+    secrets: Vec<(String, String)>,
+    /// TOTP secret, as base32 or an otpauth:// URI
+    #[arg(long, value_parser = Totp::parse)]
+    totp: Option<Totp>
+}
+
+fn parse_custom_field(input: &str) -> Result<(String, String), String> {
+    match input.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name.to_owned(), value.to_owned())),
+        _ => Err("expected NAME=VALUE".to_owned())
+    }
+}
+
+impl EntryFields {
+
+    fn apply(self, password: &mut Password) {
+        if !self.urls.is_empty() {
+            password.urls = self.urls;
+        }
+        if !self.tags.is_empty() {
+            password.tags = self.tags;
+        }
+        if let Some(notes) = self.notes {
+            password.notes = notes;
+        }
+        if let Some(favorite) = self.favorite {
+            password.favorite = favorite;
+        }
+        if let Some(totp) = self.totp {
+            password.totp = Some(totp);
+        }
+        let fields = self.fields.into_iter().map(|field| (field, false));
+        let secrets = self.secrets.into_iter().map(|field| (field, true));
+        for ((name, value), hidden) in fields.chain(secrets) {
+            password.custom_fields.retain(|field| field.name != name);
+            password.custom_fields.push(CustomField { name, value: SecretString::new(value), hidden });
+        }
+    }
+
+}
+
+#[derive(Subcommand)]
+pub enum BackupAction {
+    /// List backups, newest first
+    #[command(alias = "ls")]
+    List,
+    /// Replace the vault with a backup. The backup has to open with the current passphrase,
+    /// and the vault as it is now is backed up first
+    Restore {
+        /// File name of the backup as listed, or a path to one
+        backup: String,
+        /// Don't ask before replacing the vault
+        #[arg(short, long)]
+        yes: bool
+    }
+}
+
+#[derive(Subcommand)]
+pub enum FolderAction {
+    /// Show the folder tree with the number of entries in each folder
+    #[command(alias = "ls")]
+    List,
+    /// Add a folder by its path like Work/Servers, folders on the way are added too
+    Add {
+        path: String
+    },
+    /// Rename a folder
+    Rename {
+        path: String,
+        name: String
+    },
+    /// Remove a folder. Its entries and folders move up a level
+    Rm {
+        path: String
+    }
+}
+
+#[derive(Subcommand)]
+pub enum TrashAction {
+    /// List deleted entries, the most recently deleted first
+    #[command(alias = "ls")]
+    List,
+    /// Take an entry back out of the trash
+    Restore {
+        title: String
+    },
+    /// Delete an entry in the trash for good
+    Purge {
+        title: String
+    },
+    /// Delete everything in the trash for good
+    Empty
+}
+
+#[derive(Subcommand)]
+pub enum VaultAction {
+    /// List vault profiles
+    Ls,
+    /// Add a vault profile pointing at a vault file
+    Add {
+        name: String,
+        path: PathBuf
+    },
+    /// Remove a vault profile, the vault file itself is left alone
+    Rm {
+        name: String
+    },
+    /// Use this vault profile when no other vault is given
+    Default {
+        name: String
+    }
+}
+
+pub fn run(command: Command, config: &mut Config, vault: &Path) -> Result<(), Box<dyn Error>> {
+    // these don't touch the vault, so there is no need to ask for the passphrase
+    let command = match command {
+        Command::Vault { action } => return run_vault(action, config),
+        Command::Backup { action: BackupAction::List } => {
+            for backup in crate::backup::list(vault)? {
+                println!("{}\t{}", backup.created.format("%Y-%m-%d %H:%M:%S"), backup.name());
+            }
+            return Ok(());
+        }
+        Command::Generate(args) => return generate(args, config),
+        Command::Init => {
+            if vault.exists() {
+                return Err(format!("{} already exists", vault.display()).into());
+            }
+            crate::setup::create_vault(vault)?;
+            return Ok(());
+        }
+        command => command
+    };
+
+    let (mut db, key) = crate::unlock(vault, config, false);
+    match command {
+        Command::Get { title, username, field, totp, copy } => {
+            let password = find(&db, &title)?;
+            db.mark_used(password.id, chrono::Utc::now().timestamp())?;
+            let content = if let Some(name) = field {
+                match password.custom_fields.into_iter().find(|field| field.name == name) {
+                    Some(field) => field.value,
+                    None => return Err(format!("'{}' has no field named '{}'", title, name).into())
+                }
+            }else if totp {
+                match password.totp {
+                    Some(totp) => SecretString::new(totp.now().0),
+                    None => return Err(format!("'{}' has no TOTP secret", title).into())
+                }
+            }else if username {
+                SecretString::new(password.username)
+            }else {
+                password.password
+            };
+            if copy {
+                crate::clipboard::copy_and_wait(content, config.clipboard_timeout)?;
+            }else {
+                println!("{}", content.expose());
+            }
+        }
+        Command::Add { title, username, password, fields } => {
+            let password = SecretString::new(match password {
+                Some(p) => p,
+                None => rpassword::prompt_password("Password: ")?
+            });
+            let mut password = Password::new(title, username, password);
+            fields.apply(&mut password);
+            db.insert(&password)?;
+        }
+        Command::Edit { title, new_title, username, password, fields } => {
+            let mut entry = find(&db, &title)?;
+            if let Some(new_title) = new_title {
+                entry.title = new_title;
+            }
+            if let Some(username) = username {
+                entry.username = username;
+            }
+            if let Some(password) = password {
+                entry.password = SecretString::new(password);
+            }
+            fields.apply(&mut entry);
+            entry.modified_at = chrono::Utc::now().timestamp();
+            db.update(entry.id, &entry)?;
+        }
+        Command::Show { title } => {
+            let password = find(&db, &title)?;
+            println!("Title:     {}", password.title);
+            println!("Username:  {}", password.username);
+            println!("URLs:      {}", password.urls.join(" "));
+            println!("Tags:      {}", password.tags.join(", "));
+            println!("Favorite:  {}", if password.favorite { "yes" } else { "no" });
+            if let Some(id) = password.folder {
+                println!("Folder:    {}", crate::folders::path(&db.load_folders()?, id));
+            }
+            println!("TOTP:      {}", if password.totp.is_some() { "yes" } else { "no" });
+            println!("Created:   {}", crate::format_time(password.created_at));
+            println!("Modified:  {}", crate::format_time(password.modified_at));
+            println!("Last used: {}", password.last_used_at.map(crate::format_time).unwrap_or_else(|| "never".to_owned()));
+            for field in &password.custom_fields {
+                println!("{}: {}", field.name, field.display_value(false));
+            }
+            println!("Notes:\n{}", password.notes);
+        }
+        Command::History { title } => {
+            let password = find(&db, &title)?;
+            for old in db.history(password.id)? {
+                println!("{}\t{}", crate::format_time(old.replaced_at), old.password.expose());
+            }
+        }
+        Command::Mv { title, folder } => {
+            let password = find(&db, &title)?;
+            let folder = match folder {
+                Some(path) => Some(find_folder(&db.load_folders()?, &path)?),
+                None => None
+            };
+            db.move_to_folder(password.id, folder)?;
+        }
+        Command::Folder { action: FolderAction::List } => {
+            let folders = db.load_folders()?;
+            let counts = crate::folders::counts(&folders, &db.load()?);
+            for row in crate::folders::rows(&folders, &Default::default()) {
+                let folder = folders.iter().find(|folder| folder.id == row.id).unwrap();
+                println!("{}{} ({})", "  ".repeat(row.depth), folder.name, counts.get(&row.id).copied().unwrap_or(0));
+            }
+        }
+        Command::Folder { action: FolderAction::Add { path } } => {
+            let mut folders = db.load_folders()?;
+            let mut parent = None;
+            for name in path.split('/').filter(|name| !name.is_empty()) {
+                let existing = folders.iter().find(|folder| folder.parent == parent && folder.name == name);
+                parent = Some(match existing {
+                    Some(folder) => folder.id,
+                    None => {
+                        crate::folders::check_name(&folders, parent, name)?;
+                        let id = db.insert_folder(parent, name)?;
+                        folders.push(Folder { id, parent, name: name.to_owned() });
+                        id
+                    }
+                });
+            }
+        }
+        Command::Folder { action: FolderAction::Rename { path, name } } => {
+            let folders = db.load_folders()?;
+            let id = find_folder(&folders, &path)?;
+            let parent = folders.iter().find(|folder| folder.id == id).unwrap().parent;
+            crate::folders::check_name(&folders, parent, &name)?;
+            db.rename_folder(id, &name)?;
+        }
+        Command::Folder { action: FolderAction::Rm { path } } => {
+            db.delete_folder(find_folder(&db.load_folders()?, &path)?)?;
+        }
+        Command::Rm { title } => {
+            let password = find(&db, &title)?;
+            db.delete(password.id, chrono::Utc::now().timestamp())?;
+        }
+        Command::Trash { action: TrashAction::List } => {
+            for trashed in db.load_trash()? {
+                let password = trashed.password;
+                println!("{}\t{}\t{}", crate::format_time(trashed.deleted_at), password.title, password.username);
+            }
+        }
+        Command::Trash { action: TrashAction::Restore { title } } => {
+            db.restore(find_in_trash(&db, &title)?.id)?;
+        }
+        Command::Trash { action: TrashAction::Purge { title } } => {
+            db.purge(find_in_trash(&db, &title)?.id)?;
+        }
+        Command::Trash { action: TrashAction::Empty } => {
+            let count = db.purge_trash(i64::MAX)?;
+            println!("Deleted {} entries for good", count);
+        }
+        Command::Ls => {
+            print_list(&db.load()?);
+        }
+        Command::Search { query } => {
+            let items = db.load()?;
+            let found: Vec<Password> = crate::search::rank(&Query::parse(&query), &items).into_iter()
+                .cloned()
+                .collect();
+            print_list(&found);
+        }
+        Command::Audit { json, breach_list } => {
+            let passwords = db.load()?;
+            let breaches = match breach_list.as_ref().or(config.audit.breach_list.as_ref()) {
+                Some(list) => crate::hibp::check(&passwords, list)
+                    .map_err(|e| format!("{}: {}", list.display(), e))?,
+                None => HashMap::new()
+            };
+            let findings = crate::audit::audit(&passwords, &config.audit, &breaches, chrono::Utc::now().timestamp());
+            if json {
+                println!("{}", serde_json::to_string_pretty(&findings)?);
+            }else {
+                for finding in &findings {
+                    let issues: Vec<String> = finding.issues.iter().map(|issue| issue.describe()).collect();
+                    println!("{}\t{}\t{}", finding.title, finding.username, issues.join("; "));
+                }
+            }
+        }
+        Command::Import { format, file, yes, keep_duplicates, dry_run } => {
+            let (imported, paths): (Vec<Password>, Vec<Option<String>>) = crate::import::read(format, &file)?.into_iter().unzip();
+            let duplicates = crate::import::find_duplicates(&db.load()?, &imported);
+            for ((item, path), duplicate) in imported.iter().zip(&paths).zip(&duplicates) {
+                let marker = if *duplicate { "=" } else { "+" };
+                let folder = path.as_ref().map(|path| format!("\t{}", path)).unwrap_or_default();
+                println!("{} {}\t{}\t{}{}", marker, item.title, item.username, item.urls.first().map(String::as_str).unwrap_or(""), folder);
+            }
+            let duplicate_count = duplicates.iter().filter(|duplicate| **duplicate).count();
+            println!("{} new, {} already in the vault", imported.len() - duplicate_count, duplicate_count);
+            let entries: Vec<Imported> = imported.into_iter()
+                .zip(paths)
+                .zip(duplicates)
+                .filter(|(_, duplicate)| keep_duplicates || !duplicate)
+                .map(|(entry, _)| entry)
+                .collect();
+            if dry_run || entries.is_empty() {
+                return Ok(());
+            }
+            if !yes && !crate::setup::ask(&format!("Import {} entries? [y/N] ", entries.len()))? {
+                return Ok(());
+            }
+            db.insert_all(&entries)?;
+            println!("Imported {} entries", entries.len());
+        }
+        Command::Export { format, file, yes } => {
+            let passwords = db.load()?;
+            let passphrase = if format.is_plaintext() {
+                if !yes {
+                    eprintln!("WARNING: {} will hold every password, TOTP secret and hidden field UNENCRYPTED.", file.display());
+                    eprintln!("Anyone who can read the file can read your whole vault. Delete it as soon as you're done with it.");
+                    if crate::setup::prompt("Type 'yes' to write it anyway: ")? != "yes" {
+                        return Ok(());
+                    }
+                }
+                None
+            }else {
+                let passphrase = SecretString::new(rpassword::prompt_password("Export Passphrase: ")?);
+                let confirm = SecretString::new(rpassword::prompt_password("Confirm Export Passphrase: ")?);
+                if passphrase != confirm {
+                    return Err("passphrases do not match".into());
+                }
+                if passphrase.is_empty() {
+                    return Err("the export passphrase can't be empty".into());
+                }
+                Some(passphrase)
+            };
+            let data = crate::export::write(format, &passwords, &db.load_folders()?, passphrase.as_ref())?;
+            crate::export::save(&file, &data)?;
+            println!("Exported {} entries to {}", passwords.len(), file.display());
+        }
+        Command::ChangePassphrase => {
+            let new_key = crate::setup::choose_passphrase("New Passphrase: ", "Confirm New Passphrase: ")?;
+            let backup = db.change_key(&key, &new_key)?;
+            println!("Passphrase changed, the old vault was backed up to {}", backup.display());
+        }
+        Command::Backup { action: BackupAction::Restore { backup, yes } } => {
+            let path = crate::backup::find(vault, &backup)?;
+            if !yes && !crate::setup::ask(&format!("Replace {} with {}? [y/N] ", vault.display(), path.display()))? {
+                return Ok(());
+            }
+            let before = match db.restore_from(&path, &config.backups) {
+                Err(DbError::WrongKey) => return Err("the backup doesn't open with the passphrase of this vault".into()),
+                result => result?
+            };
+            println!("Restored {}, the vault as it was is in {}", path.display(), before.display());
+        }
+        Command::Vault { .. } | Command::Generate(_) | Command::Init | Command::Backup { action: BackupAction::List } => unreachable!()
+    }
+    Ok(())
+}
+
+fn generate(args: GenerateArgs, config: &Config) -> Result<(), Box<dyn Error>> {
+    let mut settings = config.generator.to_owned();
+    if let Some(length) = args.length {
+        settings.mode = GeneratorMode::Characters;
+        settings.length = length;
+    }
+    if let Some(words) = args.words {
+        settings.mode = GeneratorMode::Passphrase;
+        settings.words = words;
+    }
+    if let Some(separator) = args.separator {
+        settings.separator = separator;
+    }
+    settings.lowercase &= !args.no_lowercase;
+    settings.uppercase &= !args.no_uppercase;
+    settings.digits &= !args.no_digits;
+    settings.symbols &= !args.no_symbols;
+    settings.exclude_ambiguous |= args.exclude_ambiguous;
+    settings.must_include &= !args.no_must_include;
+
+    let generated = settings.generate()?;
+    println!("{}", generated.password.expose());
+    eprintln!("Entropy: {:.0} bits ({})", generated.bits, crate::strength::Strength::from_bits(generated.bits).label());
+    Ok(())
+}
+
+fn run_vault(action: VaultAction, config: &mut Config) -> Result<(), Box<dyn Error>> {
+    match action {
+        VaultAction::Ls => {
+            let default = config.default_vault.to_owned().unwrap_or_else(|| crate::config::DEFAULT_VAULT.to_owned());
+            for (name, path) in config.vaults() {
+                let marker = if name == default { "*" } else { " " };
+                println!("{} {}\t{}", marker, name, path.display());
+            }
+        }
+        VaultAction::Add { name, path } => {
+            let path = std::env::current_dir()?.join(path);
+            let path = path.canonicalize().unwrap_or(path);
+            config.vaults.insert(name, path);
+            config.save()?;
+        }
+        VaultAction::Rm { name } => {
+            if config.vaults.remove(&name).is_none() {
+                return Err(format!("no vault named '{}'", name).into());
+            }
+            if config.default_vault.as_ref() == Some(&name) {
+                config.default_vault = None;
+            }
+            config.save()?;
+        }
+        VaultAction::Default { name } => {
+            if !config.vaults().contains_key(&name) {
+                return Err(format!("no vault named '{}'", name).into());
+            }
+            config.default_vault = Some(name);
+            config.save()?;
+        }
+    }
+    Ok(())
+}
+
+// titles are not unique in the database, so refuse to guess when several entries match
+fn find(db: &Database, title: &str) -> Result<Password, Box<dyn Error>> {
+    let mut matches: Vec<Password> = db.load()?.into_iter()
+        .filter(|item| item.title == title)
+        .collect();
+    match matches.len() {
+        0 => Err(format!("no entry titled '{}'", title).into()),
+        1 => Ok(matches.remove(0)),
+        n => Err(format!("{} entries are titled '{}'", n, title).into())
+    }
+}
+
+fn find_folder(folders: &[Folder], path: &str) -> Result<usize, Box<dyn Error>> {
+    crate::folders::find(folders, path).ok_or_else(|| format!("no folder '{}'", path).into())
+}
+
+fn find_in_trash(db: &Database, title: &str) -> Result<Password, Box<dyn Error>> {
+    let mut matches: Vec<Password> = db.load_trash()?.into_iter()
+        .map(|trashed| trashed.password)
+        .filter(|item| item.title == title)
+        .collect();
+    match matches.len() {
+        0 => Err(format!("no entry titled '{}' in the trash", title).into()),
+        1 => Ok(matches.remove(0)),
+        n => Err(format!("{} entries in the trash are titled '{}'", n, title).into())
+    }
+}
+
+fn print_list(items: &[Password]) {
+    for item in items {
+        println!("{}\t{}", item.title, item.username);
+    }
+}
@@ -0,0 +1,87 @@
+use std::error::Error;
+use std::io::Write;
+use std::time::{Duration, Instant};
+use arboard::Clipboard;
+use zeroize::Zeroizing;
+use crate::secret::SecretString;
+
+/// Keeps track of what passmng put on the clipboard, so it can be cleared again once the
+/// timeout runs out. A timeout of zero leaves the clipboard alone.
+pub struct ClipboardTimer {
+    timeout: Duration,
+    copied: Option<(SecretString, Instant)>
+}
+
+impl ClipboardTimer {
+
+    pub fn new(timeout_secs: u64) -> ClipboardTimer {
+        ClipboardTimer {
+            timeout: Duration::from_secs(timeout_secs),
+            copied: None
+        }
+    }
+
+    pub fn copy(&mut self, content: SecretString) -> Result<(), arboard::Error> {
+        Clipboard::new()?.set_text(content.expose())?;
+        if !self.timeout.is_zero() {
+            self.copied = Some((content, Instant::now() + self.timeout));
+        }
+        Ok(())
+    }
+
+    /// Time left until the clipboard gets cleared.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.copied.as_ref().map(|(_, deadline)| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Clears the clipboard if the timeout ran out, has to be called regularly.
+    pub fn tick(&mut self) {
+        if self.remaining() == Some(Duration::ZERO) {
+            self.clear();
+        }
+    }
+
+    /// Clears the clipboard right away, as long as it still holds what we copied.
+    pub fn clear(&mut self) {
+        if let Some((content, _)) = self.copied.take() {
+            clear_if_unchanged(content.expose());
+        }
+    }
+
+}
+
+// the user may have copied something else in the meantime, which isn't ours to throw away
+fn clear_if_unchanged(content: &str) {
+    if let Ok(mut clipboard) = Clipboard::new() {
+        if clipboard.get_text().map(|text| Zeroizing::new(text).as_str() == content).unwrap_or(false) {
+            let _ = clipboard.clear();
+        }
+    }
+}
+
+/// Copies from the command line. The process stays around for the timeout, on Linux the
+/// clipboard is owned by the process that set it, and clears the clipboard on the way out.
+pub fn copy_and_wait(content: SecretString, timeout_secs: u64) -> Result<(), Box<dyn Error>> {
+    let mut clipboard = Clipboard::new()?;
+    if timeout_secs == 0 {
+        #[cfg(target_os = "linux")]
+        {
+            use arboard::SetExtLinux;
+            eprintln!("Copied to clipboard, waiting until it is replaced...");
+            clipboard.set().wait().text(content.expose())?;
+        }
+        #[cfg(not(target_os = "linux"))]
+        clipboard.set_text(content.expose())?;
+        return Ok(());
+    }
+
+    clipboard.set_text(content.expose())?;
+    for remaining in (1..=timeout_secs).rev() {
+        eprint!("\rCopied to clipboard, clearing it in {}s ", remaining);
+        std::io::stderr().flush()?;
+        std::thread::sleep(Duration::from_secs(1));
+    }
+    clear_if_unchanged(content.expose());
+    eprintln!("\rClipboard cleared.                          ");
+    Ok(())
+}
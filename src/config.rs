@@ -0,0 +1,94 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use crate::audit::AuditSettings;
+use crate::backup::BackupSettings;
+use crate::generator::GeneratorSettings;
+
+// name of the vault that lives where passmng always kept its database
+pub const DEFAULT_VAULT: &str = "default";
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    pub default_vault: Option<String>,
+    pub vaults: BTreeMap<String, PathBuf>,
+    pub generator: GeneratorSettings,
+    // seconds until copied secrets are cleared from the clipboard, 0 keeps them there
+    pub clipboard_timeout: u64,
+    // seconds without a key press until the TUI locks itself, 0 never locks
+    pub lock_timeout: u64,
+    // earlier passwords kept per entry, 0 keeps none
+    pub password_history: usize,
+    // days deleted entries stay in the trash, 0 keeps them until they're purged by hand
+    pub trash_days: u64,
+    pub backups: BackupSettings,
+    pub audit: AuditSettings
+}
+
+impl Default for Config {
+
+    fn default() -> Config {
+        Config {
+            default_vault: None,
+            vaults: BTreeMap::new(),
+            generator: GeneratorSettings::default(),
+            clipboard_timeout: 20,
+            lock_timeout: 300,
+            password_history: 10,
+            trash_days: 30,
+            backups: BackupSettings::default(),
+            audit: AuditSettings::default()
+        }
+    }
+
+}
+
+impl Config {
+
+    pub fn path() -> PathBuf {
+        dirs::config_dir().unwrap().join("passmng.toml")
+    }
+
+    pub fn load() -> Config {
+        match std::fs::read_to_string(Config::path()) {
+            Ok(content) => match toml::from_str(&content) {
+                Ok(config) => config,
+                Err(e) => {
+                    println!("{}: {}", Config::path().display(), e);
+                    std::process::exit(1);
+                }
+            },
+            Err(_) => Config::default()
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        std::fs::write(Config::path(), toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// All named vaults, including the built-in default one unless it's been overridden.
+    pub fn vaults(&self) -> BTreeMap<String, PathBuf> {
+        let mut vaults = self.vaults.clone();
+        vaults.entry(DEFAULT_VAULT.to_owned()).or_insert_with(|| dirs::config_dir().unwrap().join("my_passmng"));
+        vaults
+    }
+
+    /// Picks the vault to open. `--vault` wins over `PASSMNG_VAULT`, which wins over the
+    /// configured default. Values that name a vault profile resolve to its path, anything
+    /// else is taken as a path.
+    pub fn resolve_vault(&self, flag: Option<&str>) -> PathBuf {
+        let env = std::env::var("PASSMNG_VAULT").ok();
+        let name = flag.map(str::to_owned)
+            .or(env)
+            .or_else(|| self.default_vault.to_owned())
+            .unwrap_or_else(|| DEFAULT_VAULT.to_owned());
+        match self.vaults().remove(&name) {
+            Some(path) => path,
+            None => std::env::current_dir().unwrap().join(name)
+        }
+    }
+
+}
@@ -1,68 +1,800 @@
-use rusqlite::{Connection, DatabaseName, params};
-use crate::Password;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use rusqlite::backup::Backup;
+use rusqlite::{Connection, DatabaseName, ErrorCode, OpenFlags, params};
+use crate::backup::BackupSettings;
+use crate::folders::Folder;
+use crate::import::Imported;
+use crate::{CustomField, Password};
+use crate::secret::SecretString;
+use crate::totp::Totp;
+
+// each entry upgrades the schema by one version and `PRAGMA user_version` records how many
+// of them a vault has been through. Only ever append here, never edit a released migration.
+const MIGRATIONS: &[&str] = &[
+    // 1: vaults from before versioning already have this table, hence the IF NOT EXISTS
+    "CREATE TABLE IF NOT EXISTS passwords(
+            id INTEGER PRIMARY KEY,
+            title TEXT NOT NULL,
+            username TEXT NOT NULL,
+            password TEXT NOT NULL
+        )
+    ",
+    // 2: urls are stored one per line and tags comma separated. Entries from before this
+    // version didn't record when they were made, so they start out at the time of the upgrade
+    "ALTER TABLE passwords ADD COLUMN urls TEXT NOT NULL DEFAULT '';
+    ALTER TABLE passwords ADD COLUMN notes TEXT NOT NULL DEFAULT '';
+    ALTER TABLE passwords ADD COLUMN tags TEXT NOT NULL DEFAULT '';
+    ALTER TABLE passwords ADD COLUMN favorite INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE passwords ADD COLUMN created_at INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE passwords ADD COLUMN modified_at INTEGER NOT NULL DEFAULT 0;
+    ALTER TABLE passwords ADD COLUMN last_used_at INTEGER;
+    UPDATE passwords SET created_at = strftime('%s', 'now'), modified_at = strftime('%s', 'now');
+    ",
+    // 3
+    "CREATE TABLE custom_fields(
+            id INTEGER PRIMARY KEY,
+            password_id INTEGER NOT NULL REFERENCES passwords(id) ON DELETE CASCADE,
+            name TEXT NOT NULL,
+            value TEXT NOT NULL,
+            hidden INTEGER NOT NULL
+        );
+    CREATE INDEX custom_fields_password_id ON custom_fields(password_id);
+    ",
+    // 4: TOTP secrets are stored as otpauth URIs
+    "ALTER TABLE passwords ADD COLUMN totp TEXT;",
+    // 5: passwords an entry had before, `replaced_at` is when they stopped being its password
+    "CREATE TABLE password_history(
+            id INTEGER PRIMARY KEY,
+            password_id INTEGER NOT NULL REFERENCES passwords(id) ON DELETE CASCADE,
+            password TEXT NOT NULL,
+            replaced_at INTEGER NOT NULL
+        );
+    CREATE INDEX password_history_password_id ON password_history(password_id);
+    ",
+    // 6: deleted entries stay in the trash until they're purged, NULL for the ones that aren't deleted
+    "ALTER TABLE passwords ADD COLUMN deleted_at INTEGER;",
+    // 7: entries without a folder are at the top level
+    "CREATE TABLE folders(
+            id INTEGER PRIMARY KEY,
+            parent_id INTEGER REFERENCES folders(id),
+            name TEXT NOT NULL
+        );
+    ALTER TABLE passwords ADD COLUMN folder_id INTEGER REFERENCES folders(id);
+    ",
+];
+
+#[derive(Debug)]
+pub enum DbError {
+    /// The passphrase doesn't open the vault, or the file isn't a vault at all.
+    WrongKey,
+    /// There is no vault at the path.
+    NotFound,
+    /// The vault was migrated by a newer passmng than this one.
+    TooNew,
+    Io(std::io::Error),
+    Sqlite(rusqlite::Error)
+}
+
+impl fmt::Display for DbError {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DbError::WrongKey => f.write_str("passphrase is not valid!"),
+            DbError::NotFound => f.write_str("vault does not exist!"),
+            DbError::TooNew => f.write_str("vault was created by a newer version of passmng"),
+            DbError::Io(e) => write!(f, "{}", e),
+            DbError::Sqlite(e) => write!(f, "{}", e)
+        }
+    }
+
+}
+
+impl std::error::Error for DbError {
+
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DbError::Io(e) => Some(e),
+            DbError::Sqlite(e) => Some(e),
+            _ => None
+        }
+    }
+
+}
+
+impl From<rusqlite::Error> for DbError {
+
+    fn from(e: rusqlite::Error) -> DbError {
+        match e.sqlite_error_code() {
+            Some(ErrorCode::NotADatabase) => DbError::WrongKey,
+            _ => DbError::Sqlite(e)
+        }
+    }
+
+}
+
+impl From<std::io::Error> for DbError {
+
+    fn from(e: std::io::Error) -> DbError {
+        DbError::Io(e)
+    }
+
+}
+
+/// A password an entry used to have.
+pub struct OldPassword {
+    pub password: SecretString,
+    pub replaced_at: i64
+}
+
+/// An entry in the trash.
+pub struct Trashed {
+    pub password: Password,
+    pub deleted_at: i64
+}
+
+// SQLCipher doesn't store the KDF iterations in the vault, so custom ones are kept in a
+// `<vault>.kdf` file next to it. Without one, SQLCipher's default is used
+pub fn kdf_file(vault: &Path) -> PathBuf {
+    let mut path = OsString::from(vault);
+    path.push(".kdf");
+    PathBuf::from(path)
+}
+
+fn read_kdf_iter(vault: &Path) -> Option<u32> {
+    std::fs::read_to_string(kdf_file(vault)).ok()?.trim().parse().ok()
+}
+
+fn write_kdf_iter(vault: &Path, kdf_iter: Option<u32>) -> Result<(), std::io::Error> {
+    match kdf_iter {
+        Some(iterations) => std::fs::write(kdf_file(vault), format!("{}\n", iterations)),
+        None => Ok(())
+    }
+}
 
 pub struct Database {
-    conn: Connection
+    conn: Connection,
+    // SQLCipher only copies between databases with the same key, so backups need it
+    key: SecretString,
+    kdf_iter: Option<u32>,
+    backups: Option<BackupSettings>,
+    // old passwords kept per entry
+    history_size: usize,
+    // a backup is taken before the first write after opening, not before every one
+    backed_up: Cell<bool>
 }
 
 impl Database {
 
-    pub fn new(key: String) -> Result<Database, rusqlite::Error> {
-        let path = dirs::config_dir().unwrap().join("my_passmng");
-        let conn = Connection::open(path)?;
+    /// Opens an existing vault with the KDF iterations in its `.kdf` file, or SQLCipher's
+    /// default when there is none.
+    pub fn new(path: &Path, key: &SecretString) -> Result<Database, DbError> {
+        let flags = OpenFlags::default() - OpenFlags::SQLITE_OPEN_CREATE;
+        let mut db = Database::open(path, key, read_kdf_iter(path), flags)?;
+        db.migrate()?;
+        Ok(db)
+    }
+
+    /// Creates a brand-new vault at `path`. Custom KDF iterations are written next to it.
+    pub fn create(path: &Path, key: &SecretString, kdf_iter: Option<u32>) -> Result<Database, DbError> {
+        let mut db = Database::open(path, key, kdf_iter, OpenFlags::default())?;
+        db.migrate()?;
+        write_kdf_iter(path, kdf_iter)?;
+        Ok(db)
+    }
+
+    fn open(path: &Path, key: &SecretString, kdf_iter: Option<u32>, flags: OpenFlags) -> Result<Database, DbError> {
+        // SQLite can't open a file that's there either, when it's a directory or can't be read
+        let conn = Connection::open_with_flags(path, flags).map_err(|e| match e.sqlite_error_code() {
+            Some(ErrorCode::CannotOpen) if !flags.contains(OpenFlags::SQLITE_OPEN_CREATE) && !path.exists() => DbError::NotFound,
+            _ => DbError::from(e)
+        })?;
         // set password to our database. without this passphrase database is not readable
         conn.pragma_update(Some(DatabaseName::Main), "KEY", key)?;
-        let db = Database { conn };
-        db.create_table()?;
-        Ok(db)
+        if let Some(iterations) = kdf_iter {
+            conn.pragma_update(Some(DatabaseName::Main), "KDF_ITER", iterations)?;
+        }
+        // custom fields and old passwords are removed along with their entry
+        conn.pragma_update(None, "foreign_keys", true)?;
+        Ok(Database { conn, key: key.clone(), kdf_iter, backups: None, history_size: 0, backed_up: Cell::new(false) })
+    }
+
+    /// Turns on backups before the first write, kept as `settings` says.
+    pub fn keep_backups(&mut self, settings: BackupSettings) {
+        self.backups = Some(settings).filter(BackupSettings::enabled);
+    }
+
+    /// Keeps the last `size` passwords of an entry when it gets a new one, 0 keeps none.
+    pub fn keep_history(&mut self, size: usize) {
+        self.history_size = size;
+    }
+
+    pub fn path(&self) -> &Path {
+        self.conn.path().unwrap()
+    }
+
+    /// Re-encrypts the vault with `new_key`. The old key is checked against the file first
+    /// and a copy of the still old-key-encrypted file is left next to it, its path is returned.
+    /// Each change gets a copy of its own, named after the time, so earlier ones stay.
+    pub fn change_key(&mut self, old_key: &SecretString, new_key: &SecretString) -> Result<PathBuf, DbError> {
+        Database::new(self.path(), old_key)?;
+        let mut backup = OsString::from(self.path());
+        backup.push(format!("-{}.bak", chrono::Local::now().format("%Y%m%d-%H%M%S")));
+        let backup = PathBuf::from(backup);
+        // create_new, so a copy from the same second is never overwritten
+        let mut copy = std::fs::OpenOptions::new().write(true).create_new(true).open(&backup)?;
+        std::io::copy(&mut std::fs::File::open(self.path())?, &mut copy)?;
+        write_kdf_iter(&backup, self.kdf_iter)?;
+        self.conn.pragma_update(Some(DatabaseName::Main), "REKEY", new_key)?;
+        self.key = new_key.clone();
+        Ok(backup)
+    }
+
+    /// Copies the vault to a new file at `path`, encrypted with the same key.
+    pub fn backup_to(&self, path: &Path) -> Result<(), DbError> {
+        let mut copy = Database::open(path, &self.key, self.kdf_iter, OpenFlags::default())?;
+        Backup::new(&self.conn, &mut copy.conn)?.run_to_completion(100, std::time::Duration::ZERO, None)?;
+        write_kdf_iter(path, self.kdf_iter)?;
+        Ok(())
+    }
+
+    /// Replaces the content of the vault with the backup at `path`. The backup has to open
+    /// with the key of this vault, and the vault as it was is backed up first.
+    pub fn restore_from(&mut self, path: &Path, settings: &BackupSettings) -> Result<PathBuf, DbError> {
+        if !path.exists() {
+            return Err(DbError::NotFound);
+        }
+        let flags = OpenFlags::SQLITE_OPEN_READ_ONLY;
+        let backup = Database::open(path, &self.key, self.kdf_iter, flags)?;
+        if backup.schema_version()? > MIGRATIONS.len() {
+            return Err(DbError::TooNew);
+        }
+        let before = crate::backup::create(self, settings)?;
+        Backup::new(&backup.conn, &mut self.conn)?.run_to_completion(100, std::time::Duration::ZERO, None)?;
+        self.migrate()?;
+        Ok(before)
+    }
+
+    // every write that changes entries calls this first
+    fn backup_once(&self) -> Result<(), DbError> {
+        if let Some(settings) = &self.backups {
+            if !self.backed_up.get() {
+                crate::backup::create(self, settings)?;
+                self.backed_up.set(true);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn schema_version(&self) -> Result<usize, DbError> {
+        Ok(self.conn.pragma_query_value(Some(DatabaseName::Main), "user_version", |row| row.get(0))?)
+    }
+
+    /// Brings the vault up to the latest schema. All pending steps run in one transaction,
+    /// so a failing step leaves the vault at the version it was.
+    pub fn migrate(&mut self) -> Result<(), DbError> {
+        let version = self.schema_version()?;
+        if version > MIGRATIONS.len() {
+            return Err(DbError::TooNew);
+        }
+        let tx = self.conn.transaction()?;
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            tx.execute_batch(migration)?;
+            tx.pragma_update(Some(DatabaseName::Main), "user_version", index + 1)?;
+        }
+        Ok(tx.commit()?)
+    }
+
+    /// The entries of the vault, without the ones in the trash.
+    pub fn load(&self) -> Result<Vec<Password>, DbError> {
+        let rows = self.load_where("deleted_at is null")?;
+        Ok(rows.into_iter().map(|(password, _)| password).collect())
+    }
+
+    /// The entries in the trash, the most recently deleted first.
+    pub fn load_trash(&self) -> Result<Vec<Trashed>, DbError> {
+        let rows = self.load_where("deleted_at is not null order by deleted_at desc")?;
+        Ok(rows.into_iter()
+            .map(|(password, deleted_at)| Trashed { password, deleted_at: deleted_at.unwrap_or_default() })
+            .collect())
+    }
+
+    // entries and when they were deleted, `condition` is the where clause
+    fn load_where(&self, condition: &str) -> Result<Vec<(Password, Option<i64>)>, DbError> {
+        let mut custom_fields = self.load_custom_fields()?;
+        let mut statement = self.conn.prepare(&format!("select * from passwords where {}", condition))?;
+        let items = statement.query_map([], |row| {
+            let id: usize = row.get("id")?;
+            let urls: String = row.get("urls")?;
+            let tags: String = row.get("tags")?;
+            let totp: Option<SecretString> = row.get("totp")?;
+            let password = Password {
+                id,
+                title: row.get("title")?,
+                username: row.get("username")?,
+                password: row.get("password")?,
+                urls: urls.lines().map(str::to_owned).collect(),
+                notes: row.get("notes")?,
+                tags: tags.split(',').filter(|t| !t.is_empty()).map(str::to_owned).collect(),
+                favorite: row.get("favorite")?,
+                created_at: row.get("created_at")?,
+                modified_at: row.get("modified_at")?,
+                last_used_at: row.get("last_used_at")?,
+                totp: totp.and_then(|uri| Totp::parse(uri.expose()).ok()),
+                folder: row.get("folder_id")?,
+                custom_fields: custom_fields.remove(&id).unwrap_or_default()
+            };
+            Ok((password, row.get("deleted_at")?))
+        })?.collect::<Result<Vec<(Password, Option<i64>)>, rusqlite::Error>>()?;
+        Ok(items)
+    }
+
+    // custom fields of every entry, grouped by the id of the entry
+    fn load_custom_fields(&self) -> Result<HashMap<usize, Vec<CustomField>>, DbError> {
+        let mut fields: HashMap<usize, Vec<CustomField>> = HashMap::new();
+        let mut statement = self.conn.prepare("select * from custom_fields order by id")?;
+        let rows = statement.query_map([], |row| {
+            let field = CustomField {
+                name: row.get("name")?,
+                value: row.get("value")?,
+                hidden: row.get("hidden")?
+            };
+            Ok((row.get("password_id")?, field))
+        })?;
+        for row in rows {
+            let (password_id, field) = row?;
+            fields.entry(password_id).or_default().push(field);
+        }
+        Ok(fields)
+    }
+
+    fn save_custom_fields(&self, password_id: usize, fields: &[CustomField]) -> Result<(), DbError> {
+        self.conn.execute(
+            "delete from custom_fields where password_id=?1",
+            params![password_id]
+        )?;
+        for field in fields {
+            self.conn.execute(
+                "insert into custom_fields (password_id, name, value, hidden) values (?1, ?2, ?3, ?4)",
+                params![password_id, field.name, field.value, field.hidden]
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Adds the entry and returns the id it got, the `id` of `password` itself is ignored.
+    pub fn insert(&self, password: &Password) -> Result<usize, DbError> {
+        self.backup_once()?;
+        let tx = self.conn.unchecked_transaction()?;
+        let id = self.insert_row(password, password.folder)?;
+        tx.commit()?;
+        Ok(id)
+    }
+
+    /// Adds all the entries in one transaction, so either all of them are added or none. Each
+    /// goes into the folder at its path, which is created when it isn't there yet.
+    pub fn insert_all(&self, entries: &[Imported]) -> Result<Vec<usize>, DbError> {
+        self.backup_once()?;
+        let tx = self.conn.unchecked_transaction()?;
+        let mut folders = self.load_folders()?;
+        let mut ids = vec![];
+        for (password, path) in entries {
+            let folder = match path {
+                Some(path) => self.folder_at(&mut folders, path)?,
+                None => None
+            };
+            ids.push(self.insert_row(password, folder)?);
+        }
+        tx.commit()?;
+        Ok(ids)
+    }
+
+    // the folder at `path`, created along with any of its parents that are missing
+    fn folder_at(&self, folders: &mut Vec<Folder>, path: &str) -> Result<Option<usize>, DbError> {
+        let mut parent = None;
+        for name in path.split('/').filter(|name| !name.trim().is_empty()) {
+            let existing = folders.iter().find(|folder| folder.parent == parent && folder.name == name);
+            parent = Some(match existing {
+                Some(folder) => folder.id,
+                None => {
+                    let id = self.insert_folder(parent, name)?;
+                    folders.push(Folder { id, parent, name: name.to_owned() });
+                    id
+                }
+            });
+        }
+        Ok(parent)
+    }
+
+    fn insert_row(&self, password: &Password, folder: Option<usize>) -> Result<usize, DbError> {
+        self.conn.execute(
+            "insert into passwords (title, username, password, urls, notes, tags, favorite, created_at, modified_at, last_used_at, totp, folder_id)
+                values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                password.title, password.username, password.password, password.urls.join("\n"), password.notes,
+                password.tags.join(","), password.favorite, password.created_at, password.modified_at, password.last_used_at,
+                password.totp.as_ref().map(Totp::to_uri), folder
+            ]
+        )?;
+        let id = self.conn.last_insert_rowid() as usize;
+        self.save_custom_fields(id, &password.custom_fields)?;
+        Ok(id)
+    }
+
+    /// Saves the changed entry. When its password changed, the old one goes to its history.
+    pub fn update(&self, id: usize, password: &Password) -> Result<(), DbError> {
+        self.backup_once()?;
+        let tx = self.conn.unchecked_transaction()?;
+        let old: SecretString = self.conn.query_row(
+            "select password from passwords where id=?1",
+            params![id],
+            |row| row.get(0)
+        )?;
+        if old != password.password {
+            self.save_history(id, &old, password.modified_at)?;
+        }
+        self.conn.execute(
+            "update passwords set title=?1, username=?2, password=?3, urls=?4, notes=?5, tags=?6, favorite=?7,
+                created_at=?8, modified_at=?9, last_used_at=?10, totp=?11, folder_id=?12 where id=?13",
+            params![
+                password.title, password.username, password.password, password.urls.join("\n"), password.notes,
+                password.tags.join(","), password.favorite, password.created_at, password.modified_at, password.last_used_at,
+                password.totp.as_ref().map(Totp::to_uri), password.folder, id
+            ]
+        )?;
+        self.save_custom_fields(id, &password.custom_fields)?;
+        Ok(tx.commit()?)
     }
 
-    pub fn create_table(&self) -> Result<(), rusqlite::Error> {
-        self.conn.execute_batch(
-            "CREATE TABLE IF NOT EXISTS passwords(
-                    id INTEGER PRIMARY KEY,
-                    title TEXT NOT NULL,
-                    username TEXT NOT NULL,
-                    password TEXT NOT NULL
-                )
-            "
+    fn save_history(&self, password_id: usize, old: &SecretString, replaced_at: i64) -> Result<(), DbError> {
+        if !old.is_empty() && self.history_size > 0 {
+            self.conn.execute(
+                "insert into password_history (password_id, password, replaced_at) values (?1, ?2, ?3)",
+                params![password_id, old, replaced_at]
+            )?;
+        }
+        // also thins out what was kept with a bigger size before
+        self.conn.execute(
+            "delete from password_history where password_id=?1 and id not in
+                (select id from password_history where password_id=?1 order by id desc limit ?2)",
+            params![password_id, self.history_size]
+        )?;
+        Ok(())
+    }
+
+    /// The earlier passwords of the entry, the most recent first.
+    pub fn history(&self, id: usize) -> Result<Vec<OldPassword>, DbError> {
+        let mut statement = self.conn.prepare("select * from password_history where password_id=?1 order by id desc")?;
+        let items = statement.query_map(params![id], |row| {
+            Ok(OldPassword {
+                password: row.get("password")?,
+                replaced_at: row.get("replaced_at")?
+            })
+        })?.collect::<Result<Vec<OldPassword>, rusqlite::Error>>()?;
+        Ok(items)
+    }
+
+    /// Puts the entry in `folder`, `None` moves it to the top level.
+    pub fn move_to_folder(&self, id: usize, folder: Option<usize>) -> Result<(), DbError> {
+        self.backup_once()?;
+        self.conn.execute(
+            "update passwords set folder_id=?1 where id=?2",
+            params![folder, id]
         )?;
         Ok(())
     }
 
-    pub fn load(&self) -> Vec<Password> {
-        let mut statement = self.conn.prepare("select * from passwords").unwrap();
-        let items: Vec<Password> = statement.query_map([], |row| {
-            let password = Password::new_with_id(
-                row.get("id").unwrap(),
-                row.get("title").unwrap(),
-                row.get("username").unwrap(),
-                row.get("password").unwrap()
-            );
-            Ok(password)
-        }).unwrap().map(|i| i.unwrap()).collect();
-        items
+    pub fn load_folders(&self) -> Result<Vec<Folder>, DbError> {
+        let mut statement = self.conn.prepare("select * from folders")?;
+        let items = statement.query_map([], |row| {
+            Ok(Folder {
+                id: row.get("id")?,
+                parent: row.get("parent_id")?,
+                name: row.get("name")?
+            })
+        })?.collect::<Result<Vec<Folder>, rusqlite::Error>>()?;
+        Ok(items)
+    }
+
+    /// Adds a folder under `parent` and returns the id it got.
+    pub fn insert_folder(&self, parent: Option<usize>, name: &str) -> Result<usize, DbError> {
+        self.backup_once()?;
+        self.conn.execute(
+            "insert into folders (parent_id, name) values (?1, ?2)",
+            params![parent, name]
+        )?;
+        Ok(self.conn.last_insert_rowid() as usize)
+    }
+
+    pub fn rename_folder(&self, id: usize, name: &str) -> Result<(), DbError> {
+        self.backup_once()?;
+        self.conn.execute(
+            "update folders set name=?1 where id=?2",
+            params![name, id]
+        )?;
+        Ok(())
     }
 
-    pub fn insert(&self, password: &Password) {
+    /// Removes the folder. What was in it, entries and folders, moves up to its parent.
+    pub fn delete_folder(&self, id: usize) -> Result<(), DbError> {
+        self.backup_once()?;
+        let tx = self.conn.unchecked_transaction()?;
+        let parent: Option<usize> = self.conn.query_row(
+            "select parent_id from folders where id=?1",
+            params![id],
+            |row| row.get(0)
+        )?;
+        self.conn.execute(
+            "update passwords set folder_id=?1 where folder_id=?2",
+            params![parent, id]
+        )?;
         self.conn.execute(
-            "insert into passwords (title, username, password) values (?1, ?2, ?3)",
-            params![password.title, password.username, password.password]
-        ).unwrap();
+            "update folders set parent_id=?1 where parent_id=?2",
+            params![parent, id]
+        )?;
+        self.conn.execute(
+            "delete from folders where id=?1",
+            params![id]
+        )?;
+        Ok(tx.commit()?)
     }
 
-    pub fn update(&self, id: usize, password: &Password) {
+    pub fn mark_used(&self, id: usize, at: i64) -> Result<(), DbError> {
         self.conn.execute(
-            "update passwords set title=?1, username=?2, password=?3 where id=?4",
-            params![password.title, password.username, password.password, id]
-        ).unwrap();
+            "update passwords set last_used_at=?1 where id=?2",
+            params![at, id]
+        )?;
+        Ok(())
     }
 
-    pub fn delete(&self, id: usize) {
+    /// Moves the entry to the trash.
+    pub fn delete(&self, id: usize, at: i64) -> Result<(), DbError> {
+        self.backup_once()?;
+        self.conn.execute(
+            "update passwords set deleted_at=?1 where id=?2",
+            params![at, id]
+        )?;
+        Ok(())
+    }
+
+    /// Takes the entry back out of the trash.
+    pub fn restore(&self, id: usize) -> Result<(), DbError> {
+        self.backup_once()?;
+        self.conn.execute(
+            "update passwords set deleted_at=null where id=?1",
+            params![id]
+        )?;
+        Ok(())
+    }
+
+    /// Deletes the entry for good, along with its custom fields and history.
+    pub fn purge(&self, id: usize) -> Result<(), DbError> {
+        self.backup_once()?;
         self.conn.execute(
             "delete from passwords where id=?1",
             params![id]
-        ).unwrap();
+        )?;
+        Ok(())
+    }
+
+    /// Deletes the entries that went to the trash before `before` for good, and returns how many.
+    pub fn purge_trash(&self, before: i64) -> Result<usize, DbError> {
+        let count: usize = self.conn.query_row(
+            "select count(*) from passwords where deleted_at < ?1",
+            params![before],
+            |row| row.get(0)
+        )?;
+        // no backup when there's nothing to purge, this runs every time the vault is opened
+        if count > 0 {
+            self.backup_once()?;
+            self.conn.execute(
+                "delete from passwords where deleted_at < ?1",
+                params![before]
+            )?;
+        }
+        Ok(count)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const KEY: &str = "correct horse";
+
+    // the schema of every released version, written out as it was rather than built from
+    // MIGRATIONS, so a change to a released migration shows up here. Add one with each migration
+    const SCHEMAS: &[&str] = &[
+        // 0: from before there were migrations
+        "CREATE TABLE passwords(id INTEGER PRIMARY KEY, title TEXT NOT NULL, username TEXT NOT NULL, password TEXT NOT NULL);",
+        // 1
+        "CREATE TABLE passwords(id INTEGER PRIMARY KEY, title TEXT NOT NULL, username TEXT NOT NULL, password TEXT NOT NULL);",
+        // 2
+        "CREATE TABLE passwords(id INTEGER PRIMARY KEY, title TEXT NOT NULL, username TEXT NOT NULL, password TEXT NOT NULL,
+            urls TEXT NOT NULL DEFAULT '', notes TEXT NOT NULL DEFAULT '', tags TEXT NOT NULL DEFAULT '', favorite INTEGER NOT NULL DEFAULT 0,
+            created_at INTEGER NOT NULL DEFAULT 0, modified_at INTEGER NOT NULL DEFAULT 0, last_used_at INTEGER);",
+        // 3
+        "CREATE TABLE passwords(id INTEGER PRIMARY KEY, title TEXT NOT NULL, username TEXT NOT NULL, password TEXT NOT NULL,
+            urls TEXT NOT NULL DEFAULT '', notes TEXT NOT NULL DEFAULT '', tags TEXT NOT NULL DEFAULT '', favorite INTEGER NOT NULL DEFAULT 0,
+            created_at INTEGER NOT NULL DEFAULT 0, modified_at INTEGER NOT NULL DEFAULT 0, last_used_at INTEGER);
+        CREATE TABLE custom_fields(id INTEGER PRIMARY KEY, password_id INTEGER NOT NULL REFERENCES passwords(id) ON DELETE CASCADE,
+            name TEXT NOT NULL, value TEXT NOT NULL, hidden INTEGER NOT NULL);
+        CREATE INDEX custom_fields_password_id ON custom_fields(password_id);",
+        // 4
+        "CREATE TABLE passwords(id INTEGER PRIMARY KEY, title TEXT NOT NULL, username TEXT NOT NULL, password TEXT NOT NULL,
+            urls TEXT NOT NULL DEFAULT '', notes TEXT NOT NULL DEFAULT '', tags TEXT NOT NULL DEFAULT '', favorite INTEGER NOT NULL DEFAULT 0,
+            created_at INTEGER NOT NULL DEFAULT 0, modified_at INTEGER NOT NULL DEFAULT 0, last_used_at INTEGER, totp TEXT);
+        CREATE TABLE custom_fields(id INTEGER PRIMARY KEY, password_id INTEGER NOT NULL REFERENCES passwords(id) ON DELETE CASCADE,
+            name TEXT NOT NULL, value TEXT NOT NULL, hidden INTEGER NOT NULL);
+        CREATE INDEX custom_fields_password_id ON custom_fields(password_id);",
+        // 5
+        "CREATE TABLE passwords(id INTEGER PRIMARY KEY, title TEXT NOT NULL, username TEXT NOT NULL, password TEXT NOT NULL,
+            urls TEXT NOT NULL DEFAULT '', notes TEXT NOT NULL DEFAULT '', tags TEXT NOT NULL DEFAULT '', favorite INTEGER NOT NULL DEFAULT 0,
+            created_at INTEGER NOT NULL DEFAULT 0, modified_at INTEGER NOT NULL DEFAULT 0, last_used_at INTEGER, totp TEXT);
+        CREATE TABLE custom_fields(id INTEGER PRIMARY KEY, password_id INTEGER NOT NULL REFERENCES passwords(id) ON DELETE CASCADE,
+            name TEXT NOT NULL, value TEXT NOT NULL, hidden INTEGER NOT NULL);
+        CREATE INDEX custom_fields_password_id ON custom_fields(password_id);
+        CREATE TABLE password_history(id INTEGER PRIMARY KEY, password_id INTEGER NOT NULL REFERENCES passwords(id) ON DELETE CASCADE,
+            password TEXT NOT NULL, replaced_at INTEGER NOT NULL);
+        CREATE INDEX password_history_password_id ON password_history(password_id);",
+        // 6
+        "CREATE TABLE passwords(id INTEGER PRIMARY KEY, title TEXT NOT NULL, username TEXT NOT NULL, password TEXT NOT NULL,
+            urls TEXT NOT NULL DEFAULT '', notes TEXT NOT NULL DEFAULT '', tags TEXT NOT NULL DEFAULT '', favorite INTEGER NOT NULL DEFAULT 0,
+            created_at INTEGER NOT NULL DEFAULT 0, modified_at INTEGER NOT NULL DEFAULT 0, last_used_at INTEGER, totp TEXT, deleted_at INTEGER);
+        CREATE TABLE custom_fields(id INTEGER PRIMARY KEY, password_id INTEGER NOT NULL REFERENCES passwords(id) ON DELETE CASCADE,
+            name TEXT NOT NULL, value TEXT NOT NULL, hidden INTEGER NOT NULL);
+        CREATE INDEX custom_fields_password_id ON custom_fields(password_id);
+        CREATE TABLE password_history(id INTEGER PRIMARY KEY, password_id INTEGER NOT NULL REFERENCES passwords(id) ON DELETE CASCADE,
+            password TEXT NOT NULL, replaced_at INTEGER NOT NULL);
+        CREATE INDEX password_history_password_id ON password_history(password_id);",
+        // 7
+        "CREATE TABLE passwords(id INTEGER PRIMARY KEY, title TEXT NOT NULL, username TEXT NOT NULL, password TEXT NOT NULL,
+            urls TEXT NOT NULL DEFAULT '', notes TEXT NOT NULL DEFAULT '', tags TEXT NOT NULL DEFAULT '', favorite INTEGER NOT NULL DEFAULT 0,
+            created_at INTEGER NOT NULL DEFAULT 0, modified_at INTEGER NOT NULL DEFAULT 0, last_used_at INTEGER, totp TEXT, deleted_at INTEGER, folder_id INTEGER REFERENCES folders(id));
+        CREATE TABLE custom_fields(id INTEGER PRIMARY KEY, password_id INTEGER NOT NULL REFERENCES passwords(id) ON DELETE CASCADE,
+            name TEXT NOT NULL, value TEXT NOT NULL, hidden INTEGER NOT NULL);
+        CREATE INDEX custom_fields_password_id ON custom_fields(password_id);
+        CREATE TABLE password_history(id INTEGER PRIMARY KEY, password_id INTEGER NOT NULL REFERENCES passwords(id) ON DELETE CASCADE,
+            password TEXT NOT NULL, replaced_at INTEGER NOT NULL);
+        CREATE INDEX password_history_password_id ON password_history(password_id);
+        CREATE TABLE folders(id INTEGER PRIMARY KEY, parent_id INTEGER REFERENCES folders(id), name TEXT NOT NULL);",
+    ];
+
+    // a vault as a passmng that knew only the first `version` migrations left it, with one entry
+    fn fixture(dir: &Path, version: usize) -> PathBuf {
+        let path = dir.join(format!("v{}", version));
+        let conn = Connection::open(&path).unwrap();
+        conn.pragma_update(Some(DatabaseName::Main), "KEY", KEY).unwrap();
+        conn.execute_batch(SCHEMAS[version]).unwrap();
+        conn.pragma_update(Some(DatabaseName::Main), "user_version", version).unwrap();
+        conn.execute("insert into passwords (title, username, password) values ('github', 'alice', 's3cret')", []).unwrap();
+        if version >= 3 {
+            conn.execute("insert into custom_fields (password_id, name, value, hidden) values (1, 'PIN', '1234', 1)", []).unwrap();
+        }
+        path
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn migrates_vaults_of_every_version() {
+        assert_eq!(SCHEMAS.len(), MIGRATIONS.len() + 1, "every migration needs its schema in SCHEMAS");
+        let dir = tempfile::tempdir().unwrap();
+        for version in 0..SCHEMAS.len() {
+            let path = fixture(dir.path(), version);
+            let db = Database::new(&path, &SecretString::from(KEY)).unwrap();
+            assert_eq!(db.schema_version().unwrap(), MIGRATIONS.len(), "vault of version {}", version);
+
+            let passwords = db.load().unwrap();
+            assert_eq!(passwords.len(), 1, "vault of version {}", version);
+            let password = &passwords[0];
+            assert_eq!((password.title.as_str(), password.username.as_str()), ("github", "alice"));
+            assert_eq!(password.password.expose(), "s3cret");
+            // the timestamp migration dates the entries it finds
+            if version < 2 {
+                assert!(password.created_at > 0);
+            }
+            assert!(password.totp.is_none() && password.folder.is_none());
+            if version >= 3 {
+                assert_eq!(password.custom_fields.len(), 1);
+                assert_eq!(password.custom_fields[0].value.expose(), "1234");
+                assert!(password.custom_fields[0].hidden);
+            }
+        }
+    }
+
+    #[test]
+    fn migrating_again_changes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = fixture(dir.path(), 0);
+        drop(Database::new(&path, &SecretString::from(KEY)).unwrap());
+        let db = Database::new(&path, &SecretString::from(KEY)).unwrap();
+        assert_eq!(db.schema_version().unwrap(), MIGRATIONS.len());
+        assert_eq!(db.load().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn refuses_vaults_from_a_newer_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = fixture(dir.path(), MIGRATIONS.len());
+        let conn = Connection::open(&path).unwrap();
+        conn.pragma_update(Some(DatabaseName::Main), "KEY", KEY).unwrap();
+        conn.pragma_update(Some(DatabaseName::Main), "user_version", MIGRATIONS.len() + 1).unwrap();
+        drop(conn);
+        assert!(matches!(Database::new(&path, &SecretString::from(KEY)), Err(DbError::TooNew)));
+    }
+
+    #[test]
+    fn add_edit_delete_act_on_the_returned_id() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Database::create(&dir.path().join("vault"), &SecretString::from(KEY), Some(4000)).unwrap();
+        db.keep_history(10);
+        let first = db.insert(&Password::new("github".to_owned(), "alice".to_owned(), SecretString::from("one"))).unwrap();
+        let second = db.insert(&Password::new("gitlab".to_owned(), "bob".to_owned(), SecretString::from("two"))).unwrap();
+        assert!(first != 0 && second != 0 && first != second);
+
+        let mut edited = db.load().unwrap().into_iter().find(|password| password.id == second).unwrap();
+        edited.username = "carol".to_owned();
+        edited.password = SecretString::from("three");
+        db.update(second, &edited).unwrap();
+
+        let passwords = db.load().unwrap();
+        let find = |id| passwords.iter().find(|password: &&Password| password.id == id).unwrap();
+        assert_eq!((find(first).username.as_str(), find(first).password.expose()), ("alice", "one"));
+        assert_eq!((find(second).username.as_str(), find(second).password.expose()), ("carol", "three"));
+        assert!(db.history(first).unwrap().is_empty());
+        assert_eq!(db.history(second).unwrap()[0].password.expose(), "two");
+
+        db.delete(first, 100).unwrap();
+        let passwords = db.load().unwrap();
+        assert_eq!(passwords.len(), 1);
+        assert_eq!(passwords[0].id, second);
+        let trash = db.load_trash().unwrap();
+        assert_eq!((trash.len(), trash[0].password.id, trash[0].deleted_at), (1, first, 100));
+
+        db.restore(first).unwrap();
+        assert_eq!(db.load().unwrap().len(), 2);
+        db.delete(second, 100).unwrap();
+        db.purge(second).unwrap();
+        assert!(db.load_trash().unwrap().is_empty());
+        assert!(db.history(second).unwrap().is_empty());
+        assert_eq!(db.load().unwrap()[0].id, first);
+    }
+
+    #[test]
+    fn import_creates_the_folders_of_the_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::create(&dir.path().join("vault"), &SecretString::from(KEY), Some(4000)).unwrap();
+        let work = db.insert_folder(None, "Work").unwrap();
+        let entry = |title: &str| Password::new(title.to_owned(), String::new(), SecretString::default());
+        db.insert_all(&[
+            (entry("top"), None),
+            (entry("work"), Some("Work".to_owned())),
+            (entry("server"), Some("/Work/Servers/".to_owned())),
+            (entry("other server"), Some("Work/Servers".to_owned()))
+        ]).unwrap();
+
+        let folders = db.load_folders().unwrap();
+        assert_eq!(folders.len(), 2);
+        let servers = crate::folders::find(&folders, "Work/Servers").unwrap();
+        let folder_of = |title: &str| db.load().unwrap().into_iter().find(|password| password.title == title).unwrap().folder;
+        assert_eq!(folder_of("top"), None);
+        assert_eq!(folder_of("work"), Some(work));
+        assert_eq!(folder_of("server"), Some(servers));
+        assert_eq!(folder_of("other server"), Some(servers));
+    }
+
+    #[test]
+    fn wrong_key_and_missing_vault() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = fixture(dir.path(), 0);
+        assert!(matches!(Database::new(&path, &SecretString::from("wrong")), Err(DbError::WrongKey)));
+        assert!(matches!(Database::new(&dir.path().join("missing"), &SecretString::from(KEY)), Err(DbError::NotFound)));
+        // a directory is there, it just isn't a vault
+        assert!(matches!(Database::new(dir.path(), &SecretString::from(KEY)), Err(DbError::Sqlite(_))));
+    }
+
+}
@@ -0,0 +1,156 @@
+// writing entries out of the vault, the reverse of import
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::io::Write;
+use std::path::Path;
+use serde::Serialize;
+use zeroize::Zeroizing;
+use crate::folders::{self, Folder};
+use crate::import::HIDDEN_COLUMN_SUFFIX;
+use crate::secret::SecretString;
+use crate::Password;
+
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Format {
+    /// Plaintext CSV, one column per custom field name, hidden ones marked with ` (hidden)`
+    Csv,
+    /// Plaintext JSON with every field
+    Json,
+    /// The JSON, encrypted with age to a passphrase of its own
+    Age
+}
+
+impl Format {
+
+    pub fn is_plaintext(&self) -> bool {
+        *self != Format::Age
+    }
+
+}
+
+#[derive(Serialize)]
+struct Entry<'a> {
+    title: &'a str,
+    username: &'a str,
+    password: &'a str,
+    urls: &'a [String],
+    notes: &'a str,
+    tags: &'a [String],
+    favorite: bool,
+    created_at: String,
+    modified_at: String,
+    last_used_at: Option<String>,
+    totp: Option<SecretString>,
+    // path like `Work/Servers`, None at the top level
+    folder: Option<String>,
+    custom_fields: Vec<Field<'a>>
+}
+
+#[derive(Serialize)]
+struct Field<'a> {
+    name: &'a str,
+    value: &'a str,
+    hidden: bool
+}
+
+/// Turns the entries into the bytes of the export, `passphrase` is only used for `Format::Age`.
+/// `folders` are the ones of the vault, each entry has the path of its folder.
+pub fn write(format: Format, passwords: &[Password], folders: &[Folder], passphrase: Option<&SecretString>) -> Result<Zeroizing<Vec<u8>>, Box<dyn Error>> {
+    match format {
+        Format::Csv => to_csv(passwords, folders),
+        Format::Json => to_json(passwords, folders),
+        Format::Age => {
+            let passphrase = passphrase.ok_or("an encrypted export needs a passphrase")?;
+            let json = to_json(passwords, folders)?;
+            let encryptor = age::Encryptor::with_user_passphrase(passphrase.expose().into());
+            let mut encrypted = Zeroizing::new(vec![]);
+            let mut writer = encryptor.wrap_output(&mut *encrypted)?;
+            writer.write_all(&json)?;
+            writer.finish()?;
+            Ok(encrypted)
+        }
+    }
+}
+
+/// Writes the export readable by the owner only, where the OS has such a thing. An existing
+/// file is never overwritten.
+pub fn save(path: &Path, data: &[u8]) -> Result<(), Box<dyn Error>> {
+    if path.exists() {
+        return Err(format!("{} already exists", path.display()).into());
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(data)?;
+    Ok(())
+}
+
+fn to_json(passwords: &[Password], folders: &[Folder]) -> Result<Zeroizing<Vec<u8>>, Box<dyn Error>> {
+    let entries: Vec<Entry> = passwords.iter()
+        .map(|password| Entry {
+            title: &password.title,
+            username: &password.username,
+            password: password.password.expose(),
+            urls: &password.urls,
+            notes: &password.notes,
+            tags: &password.tags,
+            favorite: password.favorite,
+            created_at: format_date(password.created_at),
+            modified_at: format_date(password.modified_at),
+            last_used_at: password.last_used_at.map(format_date),
+            totp: password.totp.as_ref().map(|totp| totp.to_uri()),
+            folder: password.folder.map(|id| folders::path(folders, id)),
+            custom_fields: password.custom_fields.iter()
+                .map(|field| Field { name: &field.name, value: field.value.expose(), hidden: field.hidden })
+                .collect()
+        })
+        .collect();
+    Ok(Zeroizing::new(serde_json::to_vec_pretty(&entries)?))
+}
+
+// the column names are ones the CSV importer knows, so an export can be imported again. A
+// field name can be hidden in one entry and not in another, so each gets a column of its own
+fn to_csv(passwords: &[Password], folders: &[Folder]) -> Result<Zeroizing<Vec<u8>>, Box<dyn Error>> {
+    let fields: BTreeSet<(&str, bool)> = passwords.iter()
+        .flat_map(|password| password.custom_fields.iter().map(|field| (field.name.as_str(), field.hidden)))
+        .collect();
+    let field_columns: Vec<String> = fields.iter()
+        .map(|(name, hidden)| if *hidden { format!("{}{}", name, HIDDEN_COLUMN_SUFFIX) } else { name.to_string() })
+        .collect();
+    let mut writer = ::csv::Writer::from_writer(vec![]);
+    let mut header = vec!["title", "username", "password", "url", "notes", "totp", "tags", "folder", "favorite", "created", "modified", "last_used"];
+    header.extend(field_columns.iter().map(String::as_str));
+    writer.write_record(&header)?;
+    for password in passwords {
+        let totp = password.totp.as_ref().map(|totp| totp.to_uri()).unwrap_or_default();
+        let mut record = vec![
+            password.title.to_owned(),
+            password.username.to_owned(),
+            password.password.expose().to_owned(),
+            password.urls.join(" "),
+            password.notes.to_owned(),
+            totp.expose().to_owned(),
+            password.tags.join(","),
+            password.folder.map(|id| folders::path(folders, id)).unwrap_or_default(),
+            password.favorite.to_string(),
+            format_date(password.created_at),
+            format_date(password.modified_at),
+            password.last_used_at.map(format_date).unwrap_or_default()
+        ];
+        for (name, hidden) in &fields {
+            let field = password.custom_fields.iter().find(|field| field.name == *name && field.hidden == *hidden);
+            record.push(field.map(|field| field.value.expose().to_owned()).unwrap_or_default());
+        }
+        writer.write_record(&record)?;
+        record.iter_mut().for_each(zeroize::Zeroize::zeroize);
+    }
+    Ok(Zeroizing::new(writer.into_inner()?))
+}
+
+fn format_date(timestamp: i64) -> String {
+    match chrono::DateTime::from_timestamp(timestamp, 0) {
+        Some(date) => date.to_rfc3339(),
+        None => String::new()
+    }
+}
@@ -0,0 +1,103 @@
+// the tree of folders entries are sorted into. Entries without a folder are at the top level
+use std::collections::{HashMap, HashSet};
+use crate::Password;
+
+#[derive(Clone)]
+pub struct Folder {
+    pub id: usize,
+    // None for the folders at the top level
+    pub parent: Option<usize>,
+    pub name: String
+}
+
+/// A folder as the tree shows it, `depth` is 0 for the top level.
+pub struct Row {
+    pub id: usize,
+    pub depth: usize
+}
+
+/// The folders in tree order, each one followed by its subfolders sorted by name. The
+/// subfolders of folders in `collapsed` are left out.
+pub fn rows(folders: &[Folder], collapsed: &HashSet<usize>) -> Vec<Row> {
+    let mut rows = vec![];
+    add_rows(folders, collapsed, None, 0, &mut rows);
+    rows
+}
+
+fn add_rows(folders: &[Folder], collapsed: &HashSet<usize>, parent: Option<usize>, depth: usize, rows: &mut Vec<Row>) {
+    for folder in children(folders, parent) {
+        rows.push(Row { id: folder.id, depth });
+        if !collapsed.contains(&folder.id) {
+            add_rows(folders, collapsed, Some(folder.id), depth + 1, rows);
+        }
+    }
+}
+
+pub fn children(folders: &[Folder], parent: Option<usize>) -> Vec<&Folder> {
+    let mut children: Vec<&Folder> = folders.iter().filter(|folder| folder.parent == parent).collect();
+    children.sort_by_key(|folder| folder.name.to_lowercase());
+    children
+}
+
+/// The folder and every folder below it.
+pub fn subtree(folders: &[Folder], id: usize) -> HashSet<usize> {
+    let mut ids = HashSet::from([id]);
+    let mut pending = vec![id];
+    while let Some(parent) = pending.pop() {
+        for folder in folders.iter().filter(|folder| folder.parent == Some(parent)) {
+            ids.insert(folder.id);
+            pending.push(folder.id);
+        }
+    }
+    ids
+}
+
+/// How many entries each folder holds, counting the ones in its subfolders too.
+pub fn counts(folders: &[Folder], passwords: &[Password]) -> HashMap<usize, usize> {
+    let parents: HashMap<usize, Option<usize>> = folders.iter().map(|folder| (folder.id, folder.parent)).collect();
+    let mut counts = HashMap::new();
+    for password in passwords {
+        let mut folder = password.folder;
+        while let Some(id) = folder {
+            *counts.entry(id).or_insert(0) += 1;
+            folder = parents.get(&id).copied().flatten();
+        }
+    }
+    counts
+}
+
+/// The names from the top level down to the folder, like `Work/Servers`.
+pub fn path(folders: &[Folder], id: usize) -> String {
+    let mut names = vec![];
+    let mut folder = folders.iter().find(|folder| folder.id == id);
+    while let Some(current) = folder {
+        names.push(current.name.as_str());
+        folder = current.parent.and_then(|parent| folders.iter().find(|folder| folder.id == parent));
+    }
+    names.reverse();
+    names.join("/")
+}
+
+/// Finds a folder by its path.
+pub fn find(folders: &[Folder], path: &str) -> Option<usize> {
+    let mut parent = None;
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+        parent = Some(folders.iter().find(|folder| folder.parent == parent && folder.name == name)?.id);
+    }
+    parent
+}
+
+/// Checks the name for a folder under `parent`. Paths have to lead to a single folder, so
+/// names can't have a `/` in them or be taken by another folder next to it.
+pub fn check_name(folders: &[Folder], parent: Option<usize>, name: &str) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("folder name can't be empty".to_owned());
+    }
+    if name.contains('/') {
+        return Err("folder name can't contain '/'".to_owned());
+    }
+    if folders.iter().any(|folder| folder.parent == parent && folder.name == name) {
+        return Err(format!("there already is a folder named '{}' there", name));
+    }
+    Ok(())
+}
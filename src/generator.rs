@@ -0,0 +1,134 @@
+use rand::rngs::OsRng;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+use crate::secret::SecretString;
+
+const LOWERCASE: &str = "abcdefghijklmnopqrstuvwxyz";
+const UPPERCASE: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &str = "0123456789";
+const SYMBOLS: &str = "!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+// characters that are easy to mix up when a password has to be read or typed by hand
+const AMBIGUOUS: &str = "Il1|O0o`'\"";
+
+// the BIP-39 english wordlist (CC0), 2048 words so every word adds 11 bits
+const WORDLIST: &str = include_str!("wordlist.txt");
+
+#[derive(Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub enum GeneratorMode {
+    Characters,
+    Passphrase
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GeneratorSettings {
+    pub mode: GeneratorMode,
+    pub length: usize,
+    pub lowercase: bool,
+    pub uppercase: bool,
+    pub digits: bool,
+    pub symbols: bool,
+    pub exclude_ambiguous: bool,
+    // at least one character of every enabled class
+    pub must_include: bool,
+    pub words: usize,
+    pub separator: String
+}
+
+impl Default for GeneratorSettings {
+
+    fn default() -> GeneratorSettings {
+        GeneratorSettings {
+            mode: GeneratorMode::Characters,
+            length: 20,
+            lowercase: true,
+            uppercase: true,
+            digits: true,
+            symbols: true,
+            exclude_ambiguous: false,
+            must_include: true,
+            words: 6,
+            separator: "-".to_owned()
+        }
+    }
+
+}
+
+pub struct Generated {
+    pub password: SecretString,
+    // entropy of the generator settings, which is what an attacker who knows them is up against
+    pub bits: f64
+}
+
+impl GeneratorSettings {
+
+    // the character classes that are turned on, with the ambiguous characters already taken out
+    fn classes(&self) -> Vec<Vec<char>> {
+        [(self.lowercase, LOWERCASE), (self.uppercase, UPPERCASE), (self.digits, DIGITS), (self.symbols, SYMBOLS)]
+            .iter()
+            .filter(|(enabled, _)| *enabled)
+            .map(|(_, chars)| {
+                chars.chars()
+                    .filter(|c| !self.exclude_ambiguous || !AMBIGUOUS.contains(*c))
+                    .collect()
+            })
+            .collect()
+    }
+
+    pub fn generate(&self) -> Result<Generated, String> {
+        match self.mode {
+            GeneratorMode::Characters => self.generate_characters(),
+            GeneratorMode::Passphrase => self.generate_passphrase()
+        }
+    }
+
+    fn generate_characters(&self) -> Result<Generated, String> {
+        let classes = self.classes();
+        if classes.is_empty() {
+            return Err("at least one character class is needed".to_owned());
+        }
+        if self.must_include && self.length < classes.len() {
+            return Err(format!("length must be at least {} to include every class", classes.len()));
+        }
+        if self.length == 0 {
+            return Err("length must be at least 1".to_owned());
+        }
+
+        let pool: Vec<char> = classes.concat();
+        let mut password: Vec<char> = vec![];
+        if self.must_include {
+            for class in &classes {
+                password.push(*class.choose(&mut OsRng).unwrap());
+            }
+        }
+        while password.len() < self.length {
+            password.push(*pool.choose(&mut OsRng).unwrap());
+        }
+        // the required characters were put first, so mix them in
+        password.shuffle(&mut OsRng);
+
+        let mut generated = String::with_capacity(password.len() * 4);
+        generated.extend(password.iter());
+        password.zeroize();
+        Ok(Generated {
+            password: SecretString::new(generated),
+            bits: self.length as f64 * (pool.len() as f64).log2()
+        })
+    }
+
+    fn generate_passphrase(&self) -> Result<Generated, String> {
+        if self.words == 0 {
+            return Err("a passphrase needs at least 1 word".to_owned());
+        }
+        let wordlist: Vec<&str> = WORDLIST.lines().collect();
+        let words: Vec<&str> = (0..self.words)
+            .map(|_| *wordlist.choose(&mut OsRng).unwrap())
+            .collect();
+        Ok(Generated {
+            password: SecretString::new(words.join(&self.separator)),
+            bits: self.words as f64 * (wordlist.len() as f64).log2()
+        })
+    }
+
+}
@@ -0,0 +1,107 @@
+// looks passwords up in a local copy of the Have I Been Pwned password list. Nothing is sent anywhere
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use sha1::{Digest, Sha1};
+use zeroize::Zeroizing;
+use crate::Password;
+
+// longest line of the list is a 40 character hash, a colon, the count and "\r\n"
+const MAX_LINE: usize = 64;
+
+/// The list, either as one file of `HASH:COUNT` lines sorted by hash, like the official
+/// download, or as a directory of range files named by the first 5 characters of the hash
+/// holding `SUFFIX:COUNT` lines, like the range API and the downloader with `-s false` give.
+pub enum BreachList {
+    Sorted { file: File, len: u64 },
+    Ranges(PathBuf)
+}
+
+impl BreachList {
+
+    pub fn open(path: &Path) -> Result<BreachList, std::io::Error> {
+        if path.is_dir() {
+            return Ok(BreachList::Ranges(path.to_owned()));
+        }
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        Ok(BreachList::Sorted { file, len })
+    }
+
+    /// How often the password shows up in breaches, `None` when it doesn't.
+    pub fn count(&mut self, password: &str) -> Result<Option<u64>, std::io::Error> {
+        let hash = Zeroizing::new(data_encoding::HEXUPPER.encode(&Sha1::digest(password.as_bytes())));
+        match self {
+            BreachList::Sorted { file, len } => search(file, *len, hash.as_bytes()),
+            BreachList::Ranges(dir) => {
+                let range = match std::fs::read_to_string(dir.join(format!("{}.txt", &hash[..5]))) {
+                    Ok(range) => range,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+                    Err(e) => return Err(e)
+                };
+                Ok(range.lines()
+                    .filter_map(|line| line.trim().split_once(':'))
+                    .find(|(suffix, _)| suffix.eq_ignore_ascii_case(&hash[5..]))
+                    .and_then(|(_, count)| count.parse().ok()))
+            }
+        }
+    }
+
+}
+
+/// Breach counts of the entries whose password is in the list, by entry id.
+pub fn check(passwords: &[Password], list: &Path) -> Result<HashMap<usize, u64>, std::io::Error> {
+    let mut list = BreachList::open(list)?;
+    let mut breached = HashMap::new();
+    for password in passwords.iter().filter(|password| !password.password.is_empty()) {
+        if let Some(count) = list.count(password.password.expose())? {
+            breached.insert(password.id, count);
+        }
+    }
+    Ok(breached)
+}
+
+// binary search over byte offsets. `lo` and `hi` bound where the line can start, a probe
+// in the middle of a line moves on to the start of the next one
+fn search(file: &mut File, len: u64, hash: &[u8]) -> Result<Option<u64>, std::io::Error> {
+    let (mut lo, mut hi) = (0, len);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        let start = next_line_start(file, mid)?;
+        if start >= hi {
+            hi = mid;
+            continue;
+        }
+        let line = read_at(file, start, MAX_LINE)?;
+        let end = line.iter().position(|b| *b == b'\n').map(|i| i + 1).unwrap_or(line.len());
+        let (line_hash, count) = line[..end].split_at(line.iter().position(|b| *b == b':').unwrap_or(end).min(end));
+        match line_hash.to_ascii_uppercase().as_slice().cmp(hash) {
+            std::cmp::Ordering::Equal => {
+                let count = String::from_utf8_lossy(count);
+                return Ok(Some(count.trim_start_matches(':').trim().parse().unwrap_or(1)));
+            }
+            std::cmp::Ordering::Less => lo = start + end as u64,
+            std::cmp::Ordering::Greater => hi = start
+        }
+    }
+    Ok(None)
+}
+
+fn next_line_start(file: &mut File, offset: u64) -> Result<u64, std::io::Error> {
+    if offset == 0 {
+        return Ok(0);
+    }
+    let bytes = read_at(file, offset - 1, MAX_LINE)?;
+    match bytes.iter().position(|b| *b == b'\n') {
+        Some(i) => Ok(offset + i as u64),
+        None => Ok(offset - 1 + bytes.len() as u64)
+    }
+}
+
+fn read_at(file: &mut File, offset: u64, len: usize) -> Result<Vec<u8>, std::io::Error> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut bytes = Vec::with_capacity(len);
+    file.take(len as u64).read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
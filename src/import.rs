@@ -0,0 +1,254 @@
+// reading the exports of other password managers into entries, nothing in here touches the vault
+
+mod bitwarden;
+mod csv;
+mod keepass;
+mod onepassword;
+
+use std::error::Error;
+use std::path::Path;
+use crate::secret::SecretString;
+use crate::totp::Totp;
+use crate::{CustomField, Password};
+
+/// An entry read from an export, with the path of the folder it goes into.
+pub type Imported = (Password, Option<String>);
+
+/// Marks the CSV columns of hidden custom fields, `PIN (hidden)` is the hidden field `PIN`.
+pub const HIDDEN_COLUMN_SUFFIX: &str = " (hidden)";
+
+#[derive(Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Format {
+    /// KeePass or KeePassXC database (KDBX 4)
+    Keepass,
+    /// Unencrypted Bitwarden JSON export
+    Bitwarden,
+    /// 1Password export, either a .1pux file or CSV
+    #[value(name = "1password")]
+    OnePassword,
+    /// CSV with a header row, like the ones Chrome and Firefox export
+    Csv
+}
+
+/// The entries of the export, each with the path of the folder it goes into.
+pub fn read(format: Format, path: &Path) -> Result<Vec<Imported>, Box<dyn Error>> {
+    let data = std::fs::read(path)?;
+    let is_csv = path.extension().map(|ext| ext.eq_ignore_ascii_case("csv")).unwrap_or(false);
+    match format {
+        Format::Keepass => {
+            let password = SecretString::new(rpassword::prompt_password("KeePass Password: ")?);
+            keepass::read(&data, &password)
+        }
+        Format::Bitwarden => bitwarden::read(&data),
+        Format::OnePassword if is_csv => csv::read(&data),
+        // 1Password has vaults rather than folders, everything goes to the top level
+        Format::OnePassword => Ok(onepassword::read(&data)?.into_iter().map(|entry| (entry, None)).collect()),
+        Format::Csv => csv::read(&data)
+    }
+}
+
+/// Marks the entries that are already in the vault, or earlier in the import. An entry is
+/// taken to be the same when title and username match, so a changed password still counts.
+pub fn find_duplicates(existing: &[Password], imported: &[Password]) -> Vec<bool> {
+    let same = |a: &Password, b: &Password| a.title.trim().eq_ignore_ascii_case(b.title.trim()) && a.username == b.username;
+    imported.iter().enumerate()
+        .map(|(i, entry)| {
+            existing.iter().any(|other| same(entry, other)) || imported[..i].iter().any(|other| same(entry, other))
+        })
+        .collect()
+}
+
+// exports usually have a url but not always a name, the host is the next best title
+fn title_from_url(url: &str) -> String {
+    let host = url.split_once("://").map(|(_, rest)| rest).unwrap_or(url);
+    host.split(['/', '?', '#']).next().unwrap_or(host).trim_start_matches("www.").to_owned()
+}
+
+// a secret that doesn't parse is kept as a hidden field rather than thrown away
+fn set_totp(entry: &mut Password, value: &str) {
+    if value.trim().is_empty() {
+        return;
+    }
+    match Totp::parse(value) {
+        Ok(totp) => entry.totp = Some(totp),
+        Err(_) => entry.custom_fields.push(CustomField {
+            name: "TOTP".to_owned(),
+            value: SecretString::from(value),
+            hidden: true
+        })
+    }
+}
+
+// unix time from the RFC 3339 dates that most JSON exports use
+fn parse_date(date: &str) -> Option<i64> {
+    chrono::DateTime::parse_from_rfc3339(date).ok().map(|date| date.timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::folders::Folder;
+
+    // the fixtures are made up exports in each format, see fixtures/generate.py for the binary ones
+    const KEEPASS_PASSWORD: &str = "fixture password";
+
+    fn totp(entry: &Password) -> String {
+        entry.totp.as_ref().map(|totp| totp.to_uri().expose().to_owned()).unwrap_or_default()
+    }
+
+    // (name, value, hidden) of every custom field
+    fn fields(entry: &Password) -> Vec<(&str, &str, bool)> {
+        entry.custom_fields.iter().map(|field| (field.name.as_str(), field.value.expose(), field.hidden)).collect()
+    }
+
+    fn check_keepass(data: &[u8]) {
+        let (entries, paths): (Vec<Password>, Vec<Option<String>>) = keepass::read(data, &SecretString::from(KEEPASS_PASSWORD)).unwrap().into_iter().unzip();
+        // the entry in the recycle bin is left out, the root group is the top level
+        assert_eq!(paths, [None, Some("Finance".to_owned())]);
+
+        let github = &entries[0];
+        assert_eq!((github.title.as_str(), github.username.as_str()), ("GitHub", "alice"));
+        assert_eq!(github.password.expose(), "gh-s3cret");
+        assert_eq!(github.urls, ["https://github.com/login"]);
+        assert_eq!(github.notes, "two factor is on");
+        assert_eq!(github.tags, ["work", "dev"]);
+        assert_eq!((github.created_at, github.modified_at, github.last_used_at), (1600000000, 1650000000, Some(1700000000)));
+        assert_eq!(totp(github), "otpauth://totp/passmng?secret=JBSWY3DPEHPK3PXP&algorithm=SHA1&digits=6&period=30");
+        assert_eq!(fields(github), [("Recovery code", "1111-2222", true), ("Team", "core", false)]);
+
+        let bank = &entries[1];
+        assert_eq!((bank.title.as_str(), bank.username.as_str(), bank.password.expose()), ("Bank", "bob", "b4nk"));
+        assert_eq!(totp(bank), "otpauth://totp/passmng?secret=GEZDGNBVGY3TQOJQ&algorithm=SHA256&digits=8&period=60");
+        assert!(bank.custom_fields.is_empty());
+    }
+
+    #[test]
+    fn keepass_with_aes_kdf() {
+        check_keepass(include_bytes!("import/fixtures/aes-kdf.kdbx"));
+    }
+
+    #[test]
+    fn keepass_with_argon2() {
+        check_keepass(include_bytes!("import/fixtures/argon2.kdbx"));
+    }
+
+    #[test]
+    fn keepass_with_the_wrong_password() {
+        for data in [&include_bytes!("import/fixtures/aes-kdf.kdbx")[..], &include_bytes!("import/fixtures/argon2.kdbx")[..]] {
+            let error = keepass::read(data, &SecretString::from("wrong")).err().unwrap();
+            assert_eq!(error.to_string(), "KeePass password is not valid!");
+        }
+        assert!(keepass::read(b"not a database", &SecretString::from(KEEPASS_PASSWORD)).is_err());
+    }
+
+    #[test]
+    fn bitwarden_json() {
+        let (entries, paths): (Vec<Password>, Vec<Option<String>>) = bitwarden::read(include_bytes!("import/fixtures/bitwarden.json")).unwrap().into_iter().unzip();
+        assert_eq!(paths, [Some("Work".to_owned()), None]);
+
+        let github = &entries[0];
+        assert_eq!((github.title.as_str(), github.username.as_str()), ("GitHub", "alice"));
+        assert_eq!(github.password.expose(), "gh-s3cret");
+        assert_eq!(github.urls, ["https://github.com/login"]);
+        assert_eq!(github.notes, "two factor is on");
+        assert!(github.tags.is_empty());
+        assert!(github.favorite);
+        assert_eq!((github.created_at, github.modified_at), (1600000000, 1650000000));
+        assert_eq!(totp(github), "otpauth://totp/passmng?secret=JBSWY3DPEHPK3PXP&algorithm=SHA1&digits=6&period=30");
+        assert_eq!(fields(github), [("Recovery code", "1111-2222", true), ("Team", "core", false)]);
+
+        // a card has no login, its details become custom fields
+        let card = &entries[1];
+        assert_eq!((card.title.as_str(), card.username.as_str(), card.password.expose()), ("Visa", "", ""));
+        assert!(card.totp.is_none());
+        assert_eq!(fields(card), [
+            ("brand", "Visa", false), ("cardholderName", "Alice Example", false), ("code", "123", true),
+            ("expMonth", "1", false), ("expYear", "2030", false), ("number", "4111111111111111", true)
+        ]);
+
+        assert!(bitwarden::read(br#"{"encrypted": true, "items": []}"#).is_err());
+    }
+
+    #[test]
+    fn one_password_1pux() {
+        let entries = onepassword::read(include_bytes!("import/fixtures/export.1pux")).unwrap();
+        assert_eq!(entries.len(), 2);
+
+        let github = &entries[0];
+        assert_eq!((github.title.as_str(), github.username.as_str()), ("GitHub", "alice"));
+        assert_eq!(github.password.expose(), "gh-s3cret");
+        assert_eq!(github.urls, ["https://github.com/login"]);
+        assert_eq!(github.notes, "two factor is on");
+        assert_eq!(github.tags, ["work"]);
+        assert!(github.favorite);
+        assert_eq!((github.created_at, github.modified_at), (1600000000, 1650000000));
+        assert_eq!(totp(github), "otpauth://totp/passmng?secret=JBSWY3DPEHPK3PXP&algorithm=SHA1&digits=6&period=30");
+        assert_eq!(fields(github), [("Recovery code", "1111-2222", true), ("Email", "alice@example.com", false)]);
+
+        let wifi = &entries[1];
+        assert_eq!((wifi.title.as_str(), wifi.password.expose()), ("Wi-Fi", "w1f1"));
+        assert_eq!(wifi.urls, ["http://router.local"]);
+        assert_eq!(wifi.tags, ["archived"]);
+        assert!(!wifi.favorite);
+        assert_eq!((wifi.created_at, wifi.modified_at), (1600000000, 1600000000));
+    }
+
+    #[test]
+    fn chrome_csv() {
+        let (entries, folders): (Vec<Password>, Vec<Option<String>>) = csv::read(include_bytes!("import/fixtures/chrome.csv")).unwrap().into_iter().unzip();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(folders, [None, None]);
+
+        let github = &entries[0];
+        assert_eq!((github.title.as_str(), github.username.as_str()), ("github.com", "alice"));
+        assert_eq!(github.password.expose(), "gh-s3cret");
+        assert_eq!(github.urls, ["https://github.com/login"]);
+        assert_eq!(github.notes, "two factor is on");
+        assert!(github.totp.is_none() && github.custom_fields.is_empty());
+
+        // no name, so the title comes from the url
+        let example = &entries[1];
+        assert_eq!((example.title.as_str(), example.username.as_str()), ("example.com", "bob"));
+        assert_eq!(example.password.expose(), "pa,ss\"word");
+    }
+
+    #[test]
+    fn firefox_csv() {
+        let (entries, folders): (Vec<Password>, Vec<Option<String>>) = csv::read(include_bytes!("import/fixtures/firefox.csv")).unwrap().into_iter().unzip();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(folders, [None]);
+
+        let github = &entries[0];
+        assert_eq!((github.title.as_str(), github.username.as_str()), ("github.com", "alice"));
+        assert_eq!(github.password.expose(), "gh-s3cret");
+        assert_eq!(github.urls, ["https://github.com"]);
+        assert_eq!((github.created_at, github.modified_at, github.last_used_at), (1600000000, 1650000000, Some(1700000000)));
+        // httpRealm, formActionOrigin and guid aren't worth keeping
+        assert!(github.custom_fields.is_empty());
+    }
+
+    #[test]
+    fn csv_export_reads_back() {
+        let folders = vec![
+            Folder { id: 1, parent: None, name: "Work".to_owned() },
+            Folder { id: 2, parent: Some(1), name: "Code".to_owned() }
+        ];
+        let mut github = Password::new("GitHub".to_owned(), "alice".to_owned(), SecretString::from("gh-s3cret"));
+        github.custom_fields = vec![
+            CustomField { name: "PIN".to_owned(), value: SecretString::from("1234"), hidden: true },
+            CustomField { name: "Team".to_owned(), value: SecretString::from("core"), hidden: false }
+        ];
+        github.folder = Some(2);
+        let mut bank = Password::new("Bank".to_owned(), "bob".to_owned(), SecretString::from("b4nk"));
+        // the same name as a hidden field elsewhere, but shown
+        bank.custom_fields = vec![CustomField { name: "PIN".to_owned(), value: SecretString::from("0000"), hidden: false }];
+
+        let data = crate::export::write(crate::export::Format::Csv, &[github, bank], &folders, None).unwrap();
+        let (entries, paths): (Vec<Password>, Vec<Option<String>>) = csv::read(&data).unwrap().into_iter().unzip();
+        assert_eq!(fields(&entries[0]), [("PIN", "1234", true), ("Team", "core", false)]);
+        assert_eq!(fields(&entries[1]), [("PIN", "0000", false)]);
+        assert_eq!(paths, [Some("Work/Code".to_owned()), None]);
+    }
+
+}
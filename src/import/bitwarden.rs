@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::error::Error;
+use serde::Deserialize;
+use crate::secret::SecretString;
+use crate::{CustomField, Password};
+use super::{parse_date, set_totp, Imported};
+
+const LOGIN: u8 = 1;
+const SECURE_NOTE: u8 = 2;
+// properties of cards and identities that are as sensitive as a password
+const HIDDEN_PROPERTIES: &[&str] = &["number", "code", "ssn", "passportNumber", "licenseNumber"];
+
+#[derive(Deserialize)]
+struct Export {
+    #[serde(default)]
+    encrypted: bool,
+    #[serde(default)]
+    folders: Vec<Folder>,
+    #[serde(default)]
+    items: Vec<Item>
+}
+
+#[derive(Deserialize)]
+struct Folder {
+    id: String,
+    name: String
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Item {
+    #[serde(rename = "type")]
+    kind: u8,
+    name: String,
+    notes: Option<String>,
+    #[serde(default)]
+    favorite: bool,
+    fields: Option<Vec<Field>>,
+    login: Option<Login>,
+    // cards and identities are a bag of named properties, they end up as custom fields
+    card: Option<HashMap<String, serde_json::Value>>,
+    identity: Option<HashMap<String, serde_json::Value>>,
+    folder_id: Option<String>,
+    creation_date: Option<String>,
+    revision_date: Option<String>
+}
+
+#[derive(Deserialize)]
+struct Field {
+    name: Option<String>,
+    value: Option<String>,
+    // 0 is text, 1 hidden and 2 a checkbox
+    #[serde(rename = "type")]
+    kind: u8
+}
+
+#[derive(Deserialize, Default)]
+struct Login {
+    username: Option<String>,
+    password: Option<String>,
+    totp: Option<String>,
+    uris: Option<Vec<Uri>>
+}
+
+#[derive(Deserialize)]
+struct Uri {
+    uri: Option<String>
+}
+
+pub fn read(data: &[u8]) -> Result<Vec<Imported>, Box<dyn Error>> {
+    let export: Export = serde_json::from_slice(data).map_err(|e| format!("not a Bitwarden JSON export: {}", e))?;
+    if export.encrypted {
+        return Err("encrypted Bitwarden exports can't be read, export as unencrypted JSON".into());
+    }
+    let folders: HashMap<String, String> = export.folders.into_iter()
+        .map(|folder| (folder.id, folder.name))
+        .collect();
+
+    let mut entries = vec![];
+    for item in export.items {
+        let login = item.login.unwrap_or_default();
+        let mut entry = Password::new(
+            item.name,
+            login.username.unwrap_or_default(),
+            SecretString::new(login.password.unwrap_or_default())
+        );
+        entry.urls = login.uris.unwrap_or_default().into_iter().filter_map(|uri| uri.uri).collect();
+        entry.notes = item.notes.unwrap_or_default();
+        entry.favorite = item.favorite;
+        if let Some(created) = item.creation_date.as_deref().and_then(parse_date) {
+            entry.created_at = created;
+        }
+        if let Some(modified) = item.revision_date.as_deref().and_then(parse_date) {
+            entry.modified_at = modified;
+        }
+        if let Some(totp) = login.totp {
+            set_totp(&mut entry, &totp);
+        }
+
+        for field in item.fields.unwrap_or_default() {
+            entry.custom_fields.push(CustomField {
+                name: field.name.unwrap_or_default(),
+                value: SecretString::new(field.value.unwrap_or_default()),
+                hidden: field.kind == 1
+            });
+        }
+        if !matches!(item.kind, LOGIN | SECURE_NOTE) {
+            let mut properties: Vec<(String, serde_json::Value)> = item.card.or(item.identity)
+                .unwrap_or_default()
+                .into_iter()
+                .collect();
+            properties.sort_by(|(a, _), (b, _)| a.cmp(b));
+            for (name, value) in properties {
+                if let serde_json::Value::String(value) = value {
+                    entry.custom_fields.push(CustomField {
+                        hidden: HIDDEN_PROPERTIES.contains(&name.as_str()),
+                        name,
+                        value: SecretString::new(value)
+                    });
+                }
+            }
+        }
+        // nested Bitwarden folders are named with their whole path, like "Work/Projects"
+        let folder = item.folder_id.and_then(|id| folders.get(&id)).cloned();
+        entries.push((entry, folder));
+    }
+    Ok(entries)
+}
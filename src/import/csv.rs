@@ -0,0 +1,91 @@
+use std::error::Error;
+use crate::secret::SecretString;
+use crate::{split_tags, CustomField, Password};
+use super::{parse_date, set_totp, title_from_url, Imported, HIDDEN_COLUMN_SUFFIX};
+
+// what the exporters call each column, compared in lowercase. Chrome, Firefox, Bitwarden's
+// and 1Password's CSV all fit in here
+const TITLE: &[&str] = &["title", "name"];
+const URL: &[&str] = &["url", "urls", "website", "login_uri", "login url", "origin_url"];
+const USERNAME: &[&str] = &["username", "user name", "login_username", "login name", "login"];
+const PASSWORD: &[&str] = &["password", "login_password"];
+const NOTES: &[&str] = &["notes", "note", "extra", "comments"];
+const TOTP: &[&str] = &["otpauth", "totp", "login_totp", "one-time password"];
+const TAGS: &[&str] = &["tags", "grouping"];
+// a path like `Work/Servers`, Bitwarden's CSV has the folder name here
+const FOLDER: &[&str] = &["folder"];
+const FAVORITE: &[&str] = &["favorite", "fav"];
+// Firefox has them in milliseconds, passmng's own export as RFC 3339
+const CREATED: &[&str] = &["timecreated", "created"];
+const MODIFIED: &[&str] = &["timepasswordchanged", "modified"];
+const LAST_USED: &[&str] = &["timelastused", "last_used"];
+// bookkeeping of the exporter that means nothing here
+const IGNORED: &[&str] = &["httprealm", "formactionorigin", "guid", "archived", "type", "reprompt"];
+
+/// The entries along with the path of the folder each one was in, if the CSV has them.
+pub fn read(data: &[u8]) -> Result<Vec<Imported>, Box<dyn Error>> {
+    let mut reader = ::csv::ReaderBuilder::new().flexible(true).from_reader(data);
+    let headers: Vec<String> = reader.headers()?.iter()
+        .map(|header| header.trim().trim_start_matches('\u{feff}').to_owned())
+        .collect();
+    let lowercase: Vec<String> = headers.iter().map(|header| header.to_lowercase()).collect();
+    let column = |names: &[&str]| lowercase.iter().position(|header| names.contains(&header.as_str()));
+    let (title, url, username, password) = (column(TITLE), column(URL), column(USERNAME), column(PASSWORD));
+    let (notes, totp, tags, favorite) = (column(NOTES), column(TOTP), column(TAGS), column(FAVORITE));
+    let (created, modified, last_used, folder) = (column(CREATED), column(MODIFIED), column(LAST_USED), column(FOLDER));
+    if password.is_none() && username.is_none() {
+        return Err("the CSV has neither a username nor a password column".into());
+    }
+    let known: Vec<usize> = [title, url, username, password, notes, totp, tags, favorite, created, modified, last_used, folder]
+        .into_iter()
+        .flatten()
+        .collect();
+
+    let mut entries = vec![];
+    for record in reader.records() {
+        let record = record?;
+        let get = |index: Option<usize>| index.and_then(|i| record.get(i)).unwrap_or("").to_owned();
+        let urls: Vec<String> = get(url).split_whitespace().map(str::to_owned).collect();
+        let mut entry_title = get(title);
+        if entry_title.is_empty() {
+            entry_title = urls.first().map(|url| title_from_url(url)).unwrap_or_else(|| get(username));
+        }
+
+        let mut entry = Password::new(entry_title, get(username), SecretString::new(get(password)));
+        entry.urls = urls;
+        entry.notes = get(notes);
+        entry.tags = split_tags(&get(tags).replace(';', ","));
+        entry.favorite = matches!(get(favorite).to_lowercase().as_str(), "1" | "true" | "yes");
+        set_totp(&mut entry, &get(totp));
+        let time = |index: Option<usize>| {
+            let value = get(index);
+            value.parse::<i64>().ok().map(|ms| ms / 1000).or_else(|| parse_date(&value))
+        };
+        if let Some(time) = time(created) {
+            entry.created_at = time;
+            entry.modified_at = time;
+        }
+        if let Some(time) = time(modified) {
+            entry.modified_at = time;
+        }
+        entry.last_used_at = time(last_used);
+
+        for (i, (header, value)) in headers.iter().zip(record.iter()).enumerate() {
+            if !known.contains(&i) && !value.is_empty() && !IGNORED.contains(&lowercase[i].as_str()) {
+                // passmng's own export marks the columns of hidden fields
+                let (name, hidden) = match header.strip_suffix(HIDDEN_COLUMN_SUFFIX) {
+                    Some(name) => (name, true),
+                    None => (header.as_str(), false)
+                };
+                entry.custom_fields.push(CustomField {
+                    name: name.to_owned(),
+                    value: SecretString::from(value),
+                    hidden
+                });
+            }
+        }
+        let path = Some(get(folder)).filter(|path| !path.trim().is_empty());
+        entries.push((entry, path));
+    }
+    Ok(entries)
+}
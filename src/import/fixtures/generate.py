@@ -0,0 +1,191 @@
+#!/usr/bin/env python3
+# Writes the binary fixtures of the importer tests: two KeePass databases and a 1Password
+# export. KDBX 4 is written from https://keepass.info/help/kb/kdbx_4.html rather than with the
+# reader under test. Needs the `cryptography` package (42+ for Argon2id). The output is the same
+# on every run, so the files only change when this script does.
+import base64, gzip, hashlib, hmac, io, json, struct, zipfile
+from pathlib import Path
+from cryptography.hazmat.primitives import padding
+from cryptography.hazmat.primitives.ciphers import Cipher, algorithms, modes
+from cryptography.hazmat.primitives.kdf.argon2 import Argon2id
+
+HERE = Path(__file__).parent
+PASSWORD = b"fixture password"
+
+SIGNATURE = bytes([0x03, 0xd9, 0xa2, 0x9a, 0x67, 0xfb, 0x4b, 0xb5])
+CIPHER_AES256 = bytes.fromhex("31c1f2e6bf714350be5805216afc5aff")
+CIPHER_CHACHA20 = bytes.fromhex("d6038a2b8b6f4cb5a524339a31dbb59a")
+KDF_AES = bytes.fromhex("c9d9f39a628a4460bf740d08c18a4fea")
+KDF_ARGON2ID = bytes.fromhex("9e298b1956db4773b23dfc3ec6f0a1e6")
+STREAM_CHACHA20 = 3
+EPOCH_OFFSET = 62135596800
+
+
+# fixed "random" bytes, so the files come out the same every time
+def seeded(name, size):
+    return hashlib.sha512(name.encode()).digest()[:size]
+
+
+def field(id, value):
+    return struct.pack("<BI", id, len(value)) + value
+
+
+def variant_dictionary(items):
+    data = struct.pack("<H", 0x0100)
+    for name, kind, value in items:
+        value = {0x04: lambda v: struct.pack("<I", v), 0x05: lambda v: struct.pack("<Q", v), 0x42: lambda v: v}[kind](value)
+        data += struct.pack("<BI", kind, len(name)) + name.encode() + struct.pack("<I", len(value)) + value
+    return data + b"\x00"
+
+
+def chacha20(key, nonce):
+    return Cipher(algorithms.ChaCha20(key, b"\x00" * 4 + nonce), mode=None).encryptor()
+
+
+def time(unix):
+    return base64.b64encode(struct.pack("<q", unix + EPOCH_OFFSET)).decode()
+
+
+class Protector:
+    # the inner stream that encrypts protected values, in document order
+    def __init__(self, key):
+        digest = hashlib.sha512(key).digest()
+        self.stream = chacha20(digest[:32], digest[32:44])
+
+    def value(self, text):
+        return base64.b64encode(self.stream.update(text.encode())).decode()
+
+
+def string(protector, key, value, protected=False):
+    if protected:
+        return f"<String><Key>{key}</Key><Value Protected=\"True\">{protector.value(value)}</Value></String>"
+    return f"<String><Key>{key}</Key><Value>{value}</Value></String>"
+
+
+def xml(protector):
+    p = protector
+    github = "".join([
+        "<Entry><UUID>AAAAAAAAAAAAAAAAAAAAAQ==</UUID>",
+        string(p, "Title", "GitHub"),
+        string(p, "UserName", "alice"),
+        string(p, "Password", "gh-s3cret", True),
+        string(p, "URL", "https://github.com/login"),
+        string(p, "Notes", "two factor is on"),
+        string(p, "otp", "otpauth://totp/GitHub:alice?secret=JBSWY3DPEHPK3PXP&amp;issuer=GitHub"),
+        string(p, "Recovery code", "1111-2222", True),
+        string(p, "Team", "core"),
+        "<Tags>work;dev</Tags>",
+        f"<Times><CreationTime>{time(1600000000)}</CreationTime><LastModificationTime>{time(1650000000)}</LastModificationTime>",
+        f"<LastAccessTime>{time(1700000000)}</LastAccessTime></Times>",
+        # the old password in the history also takes its turn in the inner stream
+        "<History><Entry>", string(p, "Title", "GitHub"), string(p, "Password", "old-s3cret", True), "</Entry></History>",
+        "</Entry>"
+    ])
+    bank = "".join([
+        "<Entry><UUID>AAAAAAAAAAAAAAAAAAAAAg==</UUID>",
+        string(p, "Title", "Bank"),
+        string(p, "UserName", "bob"),
+        string(p, "Password", "b4nk", True),
+        string(p, "TimeOtp-Secret-Base32", "GEZDGNBVGY3TQOJQ", True),
+        string(p, "TimeOtp-Length", "8"),
+        string(p, "TimeOtp-Period", "60"),
+        string(p, "TimeOtp-Algorithm", "HMAC-SHA-256"),
+        "</Entry>"
+    ])
+    deleted = "".join([
+        "<Entry><UUID>AAAAAAAAAAAAAAAAAAAAAw==</UUID>",
+        string(p, "Title", "Deleted"),
+        string(p, "Password", "gone", True),
+        "</Entry>"
+    ])
+    return "".join([
+        '<?xml version="1.0" encoding="utf-8" standalone="yes"?>',
+        "<KeePassFile><Meta><Generator>generate.py</Generator><RecycleBinUUID>AAAAAAAAAAAAAAAAAAAA/w==</RecycleBinUUID></Meta>",
+        "<Root><Group><UUID>AAAAAAAAAAAAAAAAAAAAAA==</UUID><Name>Root</Name>", github,
+        "<Group><UUID>AAAAAAAAAAAAAAAAAAAAEA==</UUID><Name>Finance</Name>", bank, "</Group>",
+        "<Group><UUID>AAAAAAAAAAAAAAAAAAAA/w==</UUID><Name>Recycle Bin</Name>", deleted, "</Group>",
+        "</Group></Root></KeePassFile>"
+    ]).encode()
+
+
+def kdbx(name, cipher, kdf, compressed):
+    master_seed = seeded(name + "seed", 32)
+    composite = hashlib.sha256(hashlib.sha256(PASSWORD).digest()).digest()
+    salt = seeded(name + "salt", 32)
+    if kdf == "aes":
+        rounds = 1000
+        parameters = [("$UUID", 0x42, KDF_AES), ("R", 0x05, rounds), ("S", 0x42, salt)]
+        encryptor = Cipher(algorithms.AES(salt), modes.ECB()).encryptor()
+        transformed = composite
+        for _ in range(rounds):
+            transformed = encryptor.update(transformed)
+        transformed = hashlib.sha256(transformed).digest()
+    else:
+        parameters = [("$UUID", 0x42, KDF_ARGON2ID), ("S", 0x42, salt), ("P", 0x04, 1),
+                      ("M", 0x05, 1024 * 1024), ("I", 0x05, 2), ("V", 0x04, 0x13)]
+        transformed = Argon2id(salt=salt, length=32, iterations=2, lanes=1, memory_cost=1024).derive(composite)
+    iv = seeded(name + "iv", 16 if cipher == CIPHER_AES256 else 12)
+
+    header = SIGNATURE + struct.pack("<HH", 0, 4)
+    header += field(2, cipher) + field(3, struct.pack("<I", 1 if compressed else 0)) + field(4, master_seed)
+    header += field(7, iv) + field(11, variant_dictionary(parameters)) + field(0, b"\r\n\r\n")
+
+    cipher_key = hashlib.sha256(master_seed + transformed).digest()
+    hmac_key = hashlib.sha512(master_seed + transformed + b"\x01").digest()
+    block_key = lambda index: hashlib.sha512(struct.pack("<Q", index) + hmac_key).digest()
+
+    stream_key = seeded(name + "stream", 64)
+    inner = field(1, struct.pack("<I", STREAM_CHACHA20)) + field(2, stream_key) + field(0, b"")
+    inner += xml(Protector(stream_key))
+    if compressed:
+        inner = gzip.compress(inner, mtime=0)
+    if cipher == CIPHER_AES256:
+        padder = padding.PKCS7(128).padder()
+        inner = padder.update(inner) + padder.finalize()
+        encryptor = Cipher(algorithms.AES(cipher_key), modes.CBC(iv)).encryptor()
+    else:
+        encryptor = chacha20(cipher_key, iv)
+    payload = encryptor.update(inner) + encryptor.finalize()
+
+    out = header + hashlib.sha256(header).digest()
+    out += hmac.new(block_key(2 ** 64 - 1), header, hashlib.sha256).digest()
+    for index, block in enumerate([payload, b""]):
+        size = struct.pack("<I", len(block))
+        out += hmac.new(block_key(index), struct.pack("<Q", index) + size + block, hashlib.sha256).digest() + size + block
+    (HERE / name).write_bytes(out)
+
+
+def onepux():
+    export = {"accounts": [{"vaults": [{"items": [
+        {
+            "favIndex": 1, "createdAt": 1600000000, "updatedAt": 1650000000, "state": "active",
+            "overview": {"title": "GitHub", "urls": [{"url": "https://github.com/login"}], "tags": ["work"]},
+            "details": {
+                "loginFields": [
+                    {"value": "alice", "designation": "username"},
+                    {"value": "gh-s3cret", "designation": "password"}
+                ],
+                "notesPlain": "two factor is on",
+                "sections": [{"fields": [
+                    {"title": "one-time password", "value": {"totp": "otpauth://totp/GitHub:alice?secret=JBSWY3DPEHPK3PXP"}},
+                    {"title": "Recovery code", "value": {"concealed": "1111-2222"}},
+                    {"title": "Email", "value": {"email": {"email_address": "alice@example.com"}}}
+                ]}]
+            }
+        },
+        {
+            "createdAt": 1600000000, "state": "archived",
+            "overview": {"title": "Wi-Fi", "url": "http://router.local"},
+            "details": {"password": "w1f1", "sections": []}
+        }
+    ]}]}]}
+    buffer = io.BytesIO()
+    with zipfile.ZipFile(buffer, "w", zipfile.ZIP_DEFLATED) as archive:
+        info = zipfile.ZipInfo("export.data", date_time=(2024, 1, 1, 0, 0, 0))
+        archive.writestr(info, json.dumps(export, indent=2), zipfile.ZIP_DEFLATED)
+    (HERE / "export.1pux").write_bytes(buffer.getvalue())
+
+
+kdbx("aes-kdf.kdbx", CIPHER_AES256, "aes", True)
+kdbx("argon2.kdbx", CIPHER_CHACHA20, "argon2", False)
+onepux()
@@ -0,0 +1,425 @@
+// a reader for KDBX 4 databases, as written by KeePass 2.35+ and KeePassXC. The format is
+// described at https://keepass.info/help/kb/kdbx_4.html
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::Read;
+use aes::cipher::{BlockEncrypt, BlockDecryptMut, KeyInit, KeyIvInit, StreamCipher};
+use aes::cipher::block_padding::Pkcs7;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use hmac::{Hmac, Mac};
+use roxmltree::{Document, Node, NodeId};
+use sha2::{Digest, Sha256, Sha512};
+use zeroize::Zeroizing;
+use crate::secret::SecretString;
+use crate::{split_tags, CustomField, Password};
+use super::{set_totp, Imported};
+
+const SIGNATURE: [u8; 8] = [0x03, 0xd9, 0xa2, 0x9a, 0x67, 0xfb, 0x4b, 0xb5];
+
+const CIPHER_AES256: [u8; 16] = [0x31, 0xc1, 0xf2, 0xe6, 0xbf, 0x71, 0x43, 0x50, 0xbe, 0x58, 0x05, 0x21, 0x6a, 0xfc, 0x5a, 0xff];
+const CIPHER_CHACHA20: [u8; 16] = [0xd6, 0x03, 0x8a, 0x2b, 0x8b, 0x6f, 0x4c, 0xb5, 0xa5, 0x24, 0x33, 0x9a, 0x31, 0xdb, 0xb5, 0x9a];
+const KDF_AES: [u8; 16] = [0xc9, 0xd9, 0xf3, 0x9a, 0x62, 0x8a, 0x44, 0x60, 0xbf, 0x74, 0x0d, 0x08, 0xc1, 0x8a, 0x4f, 0xea];
+const KDF_ARGON2D: [u8; 16] = [0xef, 0x63, 0x6d, 0xdf, 0x8c, 0x29, 0x44, 0x4b, 0x91, 0xf7, 0xa9, 0xa4, 0x03, 0xe3, 0x0a, 0x0c];
+const KDF_ARGON2ID: [u8; 16] = [0x9e, 0x29, 0x8b, 0x19, 0x56, 0xdb, 0x47, 0x73, 0xb2, 0x3d, 0xfc, 0x3e, 0xc6, 0xf0, 0xa1, 0xe6];
+
+// ids of the inner random stream that protected values are encrypted with
+const STREAM_SALSA20: u32 = 2;
+const STREAM_CHACHA20: u32 = 3;
+const SALSA20_NONCE: [u8; 8] = [0xe8, 0x30, 0x09, 0x4b, 0x97, 0x20, 0x5d, 0x2a];
+
+// seconds between 0001-01-01, where KDBX 4 times count from, and the unix epoch
+const EPOCH_OFFSET: i64 = 62_135_596_800;
+
+// the standard fields of an entry, every other string becomes a custom field
+const STANDARD_FIELDS: &[&str] = &["Title", "UserName", "Password", "URL", "Notes", "otp"];
+// KeePass' own TOTP settings, which are put together into one otpauth URI
+const TIME_OTP_FIELDS: &[&str] = &[
+    "TimeOtp-Secret-Base32", "TimeOtp-Secret", "TimeOtp-Secret-Hex", "TimeOtp-Secret-Base64",
+    "TimeOtp-Length", "TimeOtp-Period", "TimeOtp-Algorithm"
+];
+
+type HmacSha256 = Hmac<Sha256>;
+
+// a cursor over the little endian binary parts of the file
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize
+}
+
+impl<'a> Reader<'a> {
+
+    fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Box<dyn Error>> {
+        let bytes = self.data.get(self.pos..self.pos + len).ok_or("KeePass database is truncated")?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, Box<dyn Error>> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, Box<dyn Error>> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, Box<dyn Error>> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        &self.data[self.pos..]
+    }
+
+}
+
+// the key-value format the KDF parameters are stored in
+fn read_variant_dictionary(data: &[u8]) -> Result<HashMap<String, Vec<u8>>, Box<dyn Error>> {
+    let mut reader = Reader::new(data);
+    let version = reader.u16()?;
+    if version >> 8 != 1 {
+        return Err(format!("unsupported KDF parameters version {:#06x}", version).into());
+    }
+    let mut entries = HashMap::new();
+    loop {
+        // the type of the value isn't needed, every parameter is read by its known name
+        if reader.u8()? == 0 {
+            break;
+        }
+        let name_len = reader.u32()? as usize;
+        let name = String::from_utf8(reader.take(name_len)?.to_vec())?;
+        let value_len = reader.u32()? as usize;
+        entries.insert(name, reader.take(value_len)?.to_vec());
+    }
+    Ok(entries)
+}
+
+fn parameter<'a>(parameters: &'a HashMap<String, Vec<u8>>, name: &str) -> Result<&'a [u8], Box<dyn Error>> {
+    match parameters.get(name) {
+        Some(value) => Ok(value),
+        None => Err(format!("KDF parameter {} is missing", name).into())
+    }
+}
+
+fn parameter_u64(parameters: &HashMap<String, Vec<u8>>, name: &str) -> Result<u64, Box<dyn Error>> {
+    let value = parameter(parameters, name)?;
+    match value.len() {
+        4 => Ok(u32::from_le_bytes(value.try_into()?) as u64),
+        8 => Ok(u64::from_le_bytes(value.try_into()?)),
+        _ => Err(format!("KDF parameter {} is malformed", name).into())
+    }
+}
+
+// turns the password into the key the database is encrypted with, this is the slow part
+fn transform_key(composite: &[u8], parameters: &HashMap<String, Vec<u8>>) -> Result<Zeroizing<Vec<u8>>, Box<dyn Error>> {
+    let uuid = parameter(parameters, "$UUID")?;
+    let salt = parameter(parameters, "S")?;
+    let mut key = Zeroizing::new(vec![0u8; 32]);
+    if uuid == KDF_AES {
+        let cipher = aes::Aes256::new_from_slice(salt)?;
+        key.copy_from_slice(composite);
+        for _ in 0..parameter_u64(parameters, "R")? {
+            for block in key.chunks_exact_mut(16) {
+                cipher.encrypt_block(block.into());
+            }
+        }
+        *key = Sha256::digest(&*key).to_vec();
+    }else if uuid == KDF_ARGON2D || uuid == KDF_ARGON2ID {
+        let algorithm = if uuid == KDF_ARGON2D { argon2::Algorithm::Argon2d } else { argon2::Algorithm::Argon2id };
+        let version = match parameter_u64(parameters, "V")? {
+            0x10 => argon2::Version::V0x10,
+            _ => argon2::Version::V0x13
+        };
+        let params = argon2::Params::new(
+            (parameter_u64(parameters, "M")? / 1024) as u32,
+            parameter_u64(parameters, "I")? as u32,
+            parameter_u64(parameters, "P")? as u32,
+            Some(32)
+        ).map_err(|e| e.to_string())?;
+        argon2::Argon2::new(algorithm, version, params)
+            .hash_password_into(composite, salt, &mut key)
+            .map_err(|e| e.to_string())?;
+    }else {
+        return Err("the KeePass database uses an unknown key derivation".into());
+    }
+    Ok(key)
+}
+
+// every block, and the header as block u64::MAX, is authenticated with a key of its own
+fn block_mac(index: u64, hmac_key: &[u8]) -> Result<HmacSha256, Box<dyn Error>> {
+    let key = Zeroizing::new(Sha512::new().chain_update(index.to_le_bytes()).chain_update(hmac_key).finalize().to_vec());
+    Ok(<HmacSha256 as Mac>::new_from_slice(&key)?)
+}
+
+// decrypts the values marked as protected, in the order they appear in the XML
+enum InnerStream {
+    Salsa20(salsa20::Salsa20),
+    ChaCha20(chacha20::ChaCha20)
+}
+
+impl InnerStream {
+
+    fn new(id: u32, key: &[u8]) -> Result<InnerStream, Box<dyn Error>> {
+        match id {
+            STREAM_SALSA20 => {
+                let key = Sha256::digest(key);
+                Ok(InnerStream::Salsa20(salsa20::Salsa20::new(&key, &SALSA20_NONCE.into())))
+            }
+            STREAM_CHACHA20 => {
+                let hash = Sha512::digest(key);
+                Ok(InnerStream::ChaCha20(chacha20::ChaCha20::new_from_slices(&hash[..32], &hash[32..44])?))
+            }
+            _ => Err(format!("unsupported inner stream {}", id).into())
+        }
+    }
+
+    fn apply(&mut self, data: &mut [u8]) {
+        match self {
+            InnerStream::Salsa20(cipher) => cipher.apply_keystream(data),
+            InnerStream::ChaCha20(cipher) => cipher.apply_keystream(data)
+        }
+    }
+
+}
+
+pub fn read(data: &[u8], password: &SecretString) -> Result<Vec<Imported>, Box<dyn Error>> {
+    let mut reader = Reader::new(data);
+    if reader.take(8).ok() != Some(&SIGNATURE[..]) {
+        return Err("not a KeePass database".into());
+    }
+    let minor = reader.u16()?;
+    let major = reader.u16()?;
+    if major != 4 {
+        return Err(format!("only KDBX 4 databases can be imported, this one is KDBX {}.{}", major, minor).into());
+    }
+
+    let (mut cipher, mut compressed, mut master_seed, mut iv, mut kdf) = (None, false, None, None, None);
+    loop {
+        let id = reader.u8()?;
+        let len = reader.u32()? as usize;
+        let value = reader.take(len)?;
+        match id {
+            0 => break,
+            2 => cipher = Some(value),
+            3 => compressed = value.first() == Some(&1),
+            4 => master_seed = Some(value),
+            7 => iv = Some(value),
+            11 => kdf = Some(read_variant_dictionary(value)?),
+            _ => {}
+        }
+    }
+    let header = &data[..reader.pos];
+    let (cipher, master_seed, iv, kdf) = match (cipher, master_seed, iv, kdf) {
+        (Some(cipher), Some(master_seed), Some(iv), Some(kdf)) => (cipher, master_seed, iv, kdf),
+        _ => return Err("KeePass database header is incomplete".into())
+    };
+    if reader.take(32)? != Sha256::digest(header).as_slice() {
+        return Err("KeePass database is corrupted".into());
+    }
+    let header_hmac = reader.take(32)?;
+
+    // no key files, only the password
+    let composite = Zeroizing::new(Sha256::digest(Sha256::digest(password.expose().as_bytes())).to_vec());
+    let transformed = transform_key(&composite, &kdf)?;
+    let cipher_key = Zeroizing::new(Sha256::new().chain_update(master_seed).chain_update(&*transformed).finalize().to_vec());
+    let hmac_key = Zeroizing::new(Sha512::new().chain_update(master_seed).chain_update(&*transformed).chain_update([1]).finalize().to_vec());
+
+    // the header is authenticated with the key, so this is where a wrong password shows
+    let mut mac = block_mac(u64::MAX, &hmac_key)?;
+    mac.update(header);
+    if mac.verify_slice(header_hmac).is_err() {
+        return Err("KeePass password is not valid!".into());
+    }
+
+    let mut payload = Zeroizing::new(vec![]);
+    for index in 0u64.. {
+        let block_hmac = reader.take(32)?;
+        let size = reader.u32()?;
+        let block = reader.take(size as usize)?;
+        let mut mac = block_mac(index, &hmac_key)?;
+        mac.update(&index.to_le_bytes());
+        mac.update(&size.to_le_bytes());
+        mac.update(block);
+        if mac.verify_slice(block_hmac).is_err() {
+            return Err("KeePass database is corrupted".into());
+        }
+        if size == 0 {
+            break;
+        }
+        payload.extend_from_slice(block);
+    }
+
+    let plain_len = if cipher == CIPHER_AES256 {
+        cbc::Decryptor::<aes::Aes256>::new_from_slices(&cipher_key, iv)?
+            .decrypt_padded_mut::<Pkcs7>(&mut payload)
+            .map_err(|_| "KeePass database is corrupted")?
+            .len()
+    }else if cipher == CIPHER_CHACHA20 {
+        chacha20::ChaCha20::new_from_slices(&cipher_key, iv)?.apply_keystream(&mut payload);
+        payload.len()
+    }else {
+        return Err("only KeePass databases encrypted with AES or ChaCha20 can be imported".into());
+    };
+    payload.truncate(plain_len);
+
+    let mut inner = Zeroizing::new(vec![]);
+    if compressed {
+        flate2::read::GzDecoder::new(&payload[..]).read_to_end(&mut inner)?;
+    }else {
+        inner.extend_from_slice(&payload);
+    }
+
+    let mut reader = Reader::new(&inner);
+    let (mut stream_id, mut stream_key) = (None, None);
+    loop {
+        let id = reader.u8()?;
+        let len = reader.u32()? as usize;
+        let value = reader.take(len)?;
+        match id {
+            0 => break,
+            1 => stream_id = Some(u32::from_le_bytes(value.try_into()?)),
+            2 => stream_key = Some(value),
+            // attachments aren't imported
+            _ => {}
+        }
+    }
+    let mut stream = match (stream_id, stream_key) {
+        (Some(id), Some(key)) => InnerStream::new(id, key)?,
+        _ => return Err("KeePass database has no inner stream key".into())
+    };
+
+    let xml = std::str::from_utf8(reader.rest())?;
+    let document = Document::parse(xml)?;
+    let protected = decrypt_protected(&document, &mut stream)?;
+    let root = child(document.root_element(), "Root").ok_or("KeePass database has no entries")?;
+    let recycle_bin = child(document.root_element(), "Meta")
+        .and_then(|meta| child(meta, "RecycleBinUUID"))
+        .and_then(|uuid| uuid.text());
+
+    let mut entries = vec![];
+    for group in root.children().filter(|node| node.has_tag_name("Group")) {
+        read_group(group, None, recycle_bin, &protected, &mut entries);
+    }
+    Ok(entries)
+}
+
+// the inner stream runs over every protected value in document order, including the ones in
+// the history of entries, so they're all decrypted in one go before anything is read
+fn decrypt_protected(document: &Document, stream: &mut InnerStream) -> Result<HashMap<NodeId, SecretString>, Box<dyn Error>> {
+    let mut values = HashMap::new();
+    for node in document.descendants().filter(|node| node.has_tag_name("Value")) {
+        if node.attribute("Protected") == Some("True") {
+            let mut value = Zeroizing::new(BASE64.decode(node.text().unwrap_or(""))?);
+            stream.apply(&mut value);
+            values.insert(node.id(), SecretString::from(String::from_utf8_lossy(&value).as_ref()));
+        }
+    }
+    Ok(values)
+}
+
+// path is where the group is below the root group, whose entries go to the top level
+fn read_group(group: Node, path: Option<&str>, recycle_bin: Option<&str>, protected: &HashMap<NodeId, SecretString>, entries: &mut Vec<Imported>) {
+    let uuid = child(group, "UUID").and_then(|uuid| uuid.text());
+    if uuid.is_some() && uuid == recycle_bin {
+        return;
+    }
+    for node in group.children() {
+        if node.has_tag_name("Entry") {
+            entries.push((read_entry(node, protected), path.map(str::to_owned)));
+        }else if node.has_tag_name("Group") {
+            let name = child(node, "Name").and_then(|name| name.text()).unwrap_or("");
+            let path = match path {
+                Some(path) => format!("{}/{}", path, name),
+                None => name.to_owned()
+            };
+            read_group(node, Some(&path), recycle_bin, protected, entries);
+        }
+    }
+}
+
+fn read_entry(node: Node, protected: &HashMap<NodeId, SecretString>) -> Password {
+    // (key, value, protected) of every string of the entry
+    let strings: Vec<(&str, SecretString, bool)> = node.children()
+        .filter(|string| string.has_tag_name("String"))
+        .filter_map(|string| {
+            let key = child(string, "Key")?.text()?;
+            let value = child(string, "Value")?;
+            Some(match protected.get(&value.id()) {
+                Some(secret) => (key, secret.clone(), true),
+                None => (key, SecretString::from(value.text().unwrap_or("")), false)
+            })
+        })
+        .collect();
+    let get = |name: &str| strings.iter()
+        .find(|(key, _, _)| *key == name)
+        .map(|(_, value, _)| value.clone())
+        .unwrap_or_default();
+
+    let mut entry = Password::new(get("Title").expose().to_owned(), get("UserName").expose().to_owned(), get("Password"));
+    entry.urls = get("URL").expose().split_whitespace().map(str::to_owned).collect();
+    entry.notes = get("Notes").expose().to_owned();
+    if let Some(tags) = child(node, "Tags").and_then(|tags| tags.text()) {
+        entry.tags = split_tags(&tags.replace(';', ","));
+    }
+    if let Some(times) = child(node, "Times") {
+        let time = |name: &str| child(times, name).and_then(|time| time.text()).and_then(parse_time);
+        if let Some(created) = time("CreationTime") {
+            entry.created_at = created;
+        }
+        if let Some(modified) = time("LastModificationTime") {
+            entry.modified_at = modified;
+        }
+        entry.last_used_at = time("LastAccessTime");
+    }
+
+    let otp = get("otp");
+    if !otp.is_empty() {
+        set_totp(&mut entry, otp.expose());
+    }else if let Some(uri) = time_otp_uri(&get) {
+        set_totp(&mut entry, uri.expose());
+    }
+    for (key, value, hidden) in &strings {
+        if !STANDARD_FIELDS.contains(key) && !TIME_OTP_FIELDS.contains(key) {
+            entry.custom_fields.push(CustomField { name: key.to_string(), value: value.clone(), hidden: *hidden });
+        }
+    }
+    entry
+}
+
+// KeePass 2.47+ keeps TOTP settings in fields of their own
+fn time_otp_uri(get: &dyn Fn(&str) -> SecretString) -> Option<SecretString> {
+    let secret = get("TimeOtp-Secret-Base32");
+    if secret.is_empty() {
+        return None;
+    }
+    let mut uri = format!("otpauth://totp/keepass?secret={}", secret.expose());
+    let length = get("TimeOtp-Length");
+    if !length.is_empty() {
+        uri.push_str(&format!("&digits={}", length.expose()));
+    }
+    let period = get("TimeOtp-Period");
+    if !period.is_empty() {
+        uri.push_str(&format!("&period={}", period.expose()));
+    }
+    let algorithm = get("TimeOtp-Algorithm");
+    if !algorithm.is_empty() {
+        uri.push_str(&format!("&algorithm={}", algorithm.expose().replace("HMAC-", "").replace('-', "")));
+    }
+    Some(SecretString::new(uri))
+}
+
+// KDBX 4 stores times as base64 seconds since year 1, older writers as ISO 8601 text
+fn parse_time(text: &str) -> Option<i64> {
+    match BASE64.decode(text).ok().and_then(|bytes| <[u8; 8]>::try_from(bytes).ok()) {
+        Some(bytes) => Some(i64::from_le_bytes(bytes) - EPOCH_OFFSET),
+        None => super::parse_date(text)
+    }
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(name))
+}
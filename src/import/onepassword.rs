@@ -0,0 +1,153 @@
+use std::error::Error;
+use std::io::{Cursor, Read};
+use serde::Deserialize;
+use serde_json::Value;
+use crate::secret::SecretString;
+use crate::{CustomField, Password};
+use super::set_totp;
+
+// a .1pux file is a zip archive, the entries are in this file of it
+const EXPORT_DATA: &str = "export.data";
+
+#[derive(Deserialize)]
+struct Export {
+    accounts: Vec<Account>
+}
+
+#[derive(Deserialize)]
+struct Account {
+    vaults: Vec<Vault>
+}
+
+#[derive(Deserialize)]
+struct Vault {
+    items: Vec<Item>
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Item {
+    #[serde(default)]
+    fav_index: i64,
+    created_at: Option<i64>,
+    updated_at: Option<i64>,
+    state: Option<String>,
+    overview: Overview,
+    details: Details
+}
+
+#[derive(Deserialize)]
+struct Overview {
+    #[serde(default)]
+    title: String,
+    url: Option<String>,
+    urls: Option<Vec<Url>>,
+    tags: Option<Vec<String>>
+}
+
+#[derive(Deserialize)]
+struct Url {
+    url: String
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Details {
+    #[serde(default)]
+    login_fields: Vec<LoginField>,
+    notes_plain: Option<String>,
+    #[serde(default)]
+    sections: Vec<Section>,
+    // the password of items in the password category, which have no login fields
+    password: Option<String>
+}
+
+#[derive(Deserialize)]
+struct LoginField {
+    value: Option<String>,
+    designation: Option<String>
+}
+
+#[derive(Deserialize)]
+struct Section {
+    #[serde(default)]
+    fields: Vec<SectionField>
+}
+
+#[derive(Deserialize)]
+struct SectionField {
+    #[serde(default)]
+    title: String,
+    // an object with a single key naming the kind of value, like {"concealed": "1234"}
+    value: Value
+}
+
+pub fn read(data: &[u8]) -> Result<Vec<Password>, Box<dyn Error>> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data)).map_err(|e| format!("not a 1Password .1pux export: {}", e))?;
+    let mut json = vec![];
+    archive.by_name(EXPORT_DATA)?.read_to_end(&mut json)?;
+    let export: Export = serde_json::from_slice(&json).map_err(|e| format!("not a 1Password .1pux export: {}", e))?;
+
+    let items = export.accounts.into_iter()
+        .flat_map(|account| account.vaults)
+        .flat_map(|vault| vault.items);
+    let mut entries = vec![];
+    for item in items {
+        let login_field = |designation: &str| item.details.login_fields.iter()
+            .find(|field| field.designation.as_deref() == Some(designation))
+            .and_then(|field| field.value.to_owned());
+        let password = login_field("password").or_else(|| item.details.password.to_owned());
+        let mut entry = Password::new(
+            item.overview.title.to_owned(),
+            login_field("username").unwrap_or_default(),
+            SecretString::new(password.unwrap_or_default())
+        );
+
+        let mut urls: Vec<String> = item.overview.urls.into_iter().flatten().map(|url| url.url).collect();
+        if urls.is_empty() {
+            urls.extend(item.overview.url);
+        }
+        entry.urls = urls;
+        entry.notes = item.details.notes_plain.unwrap_or_default();
+        entry.tags = item.overview.tags.unwrap_or_default();
+        if item.state.as_deref() == Some("archived") {
+            entry.tags.push("archived".to_owned());
+        }
+        entry.favorite = item.fav_index > 0;
+        if let Some(created) = item.created_at {
+            entry.created_at = created;
+            entry.modified_at = created;
+        }
+        if let Some(updated) = item.updated_at {
+            entry.modified_at = updated;
+        }
+
+        for field in item.details.sections.into_iter().flat_map(|section| section.fields) {
+            let Some((kind, value)) = field.value.as_object().and_then(|value| value.iter().next()) else {
+                continue;
+            };
+            if kind == "totp" && entry.totp.is_none() {
+                set_totp(&mut entry, value.as_str().unwrap_or_default());
+                continue;
+            }
+            let value = match value {
+                Value::String(value) => value.to_owned(),
+                Value::Number(value) => value.to_string(),
+                Value::Object(email) => match email.get("email_address") {
+                    Some(Value::String(address)) => address.to_owned(),
+                    _ => continue
+                },
+                _ => continue
+            };
+            if !value.is_empty() {
+                entry.custom_fields.push(CustomField {
+                    name: field.title,
+                    value: SecretString::new(value),
+                    hidden: kind == "concealed"
+                });
+            }
+        }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
@@ -1,19 +1,47 @@
+mod audit;
+mod backup;
 mod db;
+mod cli;
+mod clipboard;
+mod config;
+mod export;
+mod folders;
+mod generator;
+mod hibp;
+mod import;
+mod search;
+mod secret;
+mod setup;
+mod strength;
+mod totp;
 
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
-use arboard::Clipboard;
-use crossterm::event::{DisableMouseCapture, EnableMouseCapture, KeyCode};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use clap::Parser;
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture, KeyCode, KeyModifiers};
 use crossterm::{event, execute};
 use crossterm::event::Event::Key;
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
-use rusqlite::ErrorCode;
+use chrono::TimeZone;
 use tui::backend::{Backend, CrosstermBackend};
 use tui::{Frame, Terminal};
 use tui::layout::{Alignment, Constraint, Direction, Layout, Rect};
 use tui::style::{Color, Modifier, Style};
-use tui::text::Span;
-use tui::widgets::{Block, Borders, BorderType, Clear, List, ListItem, ListState, Paragraph};
-use crate::db::Database;
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, BorderType, Clear, List, ListItem, ListState, Paragraph, Wrap};
+use crate::audit::Finding;
+use crate::cli::Cli;
+use crate::clipboard::ClipboardTimer;
+use crate::config::Config;
+use crate::db::{Database, DbError, OldPassword, Trashed};
+use crate::folders::Folder;
+use crate::generator::{Generated, GeneratorMode, GeneratorSettings};
+use crate::search::Query;
+use crate::secret::SecretString;
+use crate::strength::Strength;
+use crate::totp::Totp;
 
 const APP_KEYS_DESC: &str = r#"
 L:           List
@@ -22,21 +50,93 @@ P:           On list, It's copy the Password
 D:           On list, It's Delete
 E:           On list, It's Edit
 S:           Search
+V:           Switch vault
+K:           Change passphrase
+X:           Lock
+A:           Password audit, Enter jumps to the entry
+H:           On list, It's show the Password history
+Z:           On list, It's undo the last Delete
+T:           Trash, Enter restores the entry, D deletes it for good
+F:           Folders, A/E/D: Add/Rename/Delete, Left/Right: Fold
+M:           On list, It's move the entry to a Folder
 Insert Btn:  Insert new Password
 Tab:         Go to next field
 Shift+Tab:   Go to previous filed
+Space:       Toggle favorite
+Ctrl+G:      On password field, Generate a password
+Ctrl+R:      On password field, Peek at the password
+R:           On list, It's reveal the Password
+Shift+R:     Show/hide all secrets
+T:           On list, It's copy the TOTP code
+1-9:         On list, It's copy a custom field
+A/E/D/H:     On custom fields, Add/Edit/Delete/Hide
 Esc:         Exit insert mode
 "#;
 
+#[derive(Clone, Copy, PartialEq)]
 enum InputMode {
     Normal,
     Title,
     Username,
     Password,
+    Url,
+    Tags,
+    Totp,
+    Notes,
+    CustomFields,
+    CustomField,
+    Generator,
+    Favorite,
     Submit,
     Search,
     List,
-    Delete
+    Delete,
+    Vaults,
+    VaultKey,
+    ChangeKey,
+    Audit,
+    History,
+    Trash,
+    Purge,
+    Folders,
+    FolderName,
+    DeleteFolder,
+    MoveEntry,
+    Locked
+}
+
+// tab order of the fields in the new password form
+const FORM_FIELDS: [InputMode; 10] = [
+    InputMode::Title,
+    InputMode::Username,
+    InputMode::Password,
+    InputMode::Url,
+    InputMode::Tags,
+    InputMode::Totp,
+    InputMode::Notes,
+    InputMode::CustomFields,
+    InputMode::Favorite,
+    InputMode::Submit
+];
+
+#[derive(Clone)]
+pub struct CustomField {
+    name: String,
+    value: SecretString,
+    // hidden fields are secrets like PINs and are masked wherever they're shown
+    hidden: bool
+}
+
+impl CustomField {
+
+    pub fn display_value(&self, reveal: bool) -> String {
+        if self.hidden && !reveal {
+            "*".repeat(8)
+        }else {
+            self.value.expose().to_owned()
+        }
+    }
+
 }
 
 #[derive(Clone)]
@@ -44,74 +144,230 @@ pub struct Password {
     id: usize,
     title: String,
     username: String,
-    password: String
+    password: SecretString,
+    urls: Vec<String>,
+    notes: String,
+    tags: Vec<String>,
+    favorite: bool,
+    // unix timestamps
+    created_at: i64,
+    modified_at: i64,
+    last_used_at: Option<i64>,
+    totp: Option<Totp>,
+    // id of the folder the entry is in, None at the top level
+    folder: Option<usize>,
+    custom_fields: Vec<CustomField>
 }
 
 impl Password {
 
-    pub fn new(title: String, username: String, password: String) -> Password {
+    pub fn new(title: String, username: String, password: SecretString) -> Password {
+        let now = chrono::Utc::now().timestamp();
         Password {
             id: 0,
             title,
             username,
-            password
+            password,
+            urls: vec![],
+            notes: String::new(),
+            tags: vec![],
+            favorite: false,
+            created_at: now,
+            modified_at: now,
+            last_used_at: None,
+            totp: None,
+            folder: None,
+            custom_fields: vec![]
         }
     }
 
-    pub fn new_with_id(id: usize, title: String, username: String, password: String) -> Password {
-        Password {
-            id,
-            title,
-            username,
-            password
-        }
+}
+
+// moves the selection of a list with `len` items one up or down, stopping at either end. With
+// nothing selected it starts at the top
+fn step(state: &mut ListState, len: usize, up: bool) {
+    if len == 0 {
+        return;
     }
+    let selected = match state.selected() {
+        Some(i) if up => i.saturating_sub(1),
+        Some(i) => (i + 1).min(len - 1),
+        None => 0
+    };
+    state.select(Some(selected));
+}
+
+// a secret typed into a form, as stars unless it's peeked at
+fn masked(secret: &SecretString, peek: bool) -> String {
+    if peek {
+        secret.expose().to_owned()
+    }else {
+        "*".repeat(secret.expose().chars().count())
+    }
+}
+
+fn format_time(timestamp: i64) -> String {
+    match chrono::Local.timestamp_opt(timestamp, 0).single() {
+        Some(time) => time.format("%Y-%m-%d %H:%M").to_string(),
+        None => "-".to_owned()
+    }
+}
 
+fn split_tags(input: &str) -> Vec<String> {
+    input.split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_owned)
+        .collect()
 }
 
 struct PassMng {
-    db: Database,
+    // None while the vault is locked
+    db: Option<Database>,
+    config: Config,
+    vault_path: PathBuf,
+    vaults: Vec<(String, PathBuf)>,
+    vault_state: ListState,
+    vault_key: SecretString,
+    vault_error: Option<String>,
+    // current, new and confirmation passphrase of the change passphrase popup
+    rekey_fields: [SecretString; 3],
+    rekey_field: usize,
+    rekey_result: Option<Result<String, String>>,
     mode: InputMode,
+    // selection in the list as it's shown, which is filtered and ordered by the search
     list_state: ListState,
     passwords: Vec<Password>,
     search_txt: String,
-    search_list: Vec<Password>,
+    // secrets are masked unless shown for every entry or revealed for a single one
+    show_secrets: bool,
+    revealed: HashSet<usize>,
     new_title: String,
     new_username: String,
-    new_password: String,
+    new_password: SecretString,
+    // whether the password field shows what's typed instead of asterisks
+    peek: bool,
+    new_urls: String,
+    new_tags: String,
+    new_notes: String,
+    new_favorite: bool,
+    new_totp: SecretString,
+    // why the form couldn't be submitted
+    form_error: Option<String>,
+    new_custom_fields: Vec<CustomField>,
+    custom_fields_state: ListState,
+    // name and value in the custom field popup, and which field it edits (None adds a new one).
+    // The name isn't secret, but both go through the same input handling
+    custom_field_inputs: [SecretString; 2],
+    custom_field_input: usize,
+    custom_field_index: Option<usize>,
+    generator: GeneratorSettings,
+    generated: Result<Generated, String>,
+    clipboard: ClipboardTimer,
+    last_activity: Instant,
+    unlock_key: SecretString,
+    unlock_error: Option<String>,
+    // something that went wrong, shown in a popup until the next key press
+    error: Option<String>,
+    // entries with problems, found when the audit report is opened
+    audit: Vec<Finding>,
+    audit_state: ListState,
+    // how often the password of an entry shows up in the breach list, by entry id
+    breaches: HashMap<usize, u64>,
+    // earlier passwords of the selected entry, loaded when the history popup is opened
+    history: Vec<OldPassword>,
+    history_state: ListState,
+    // entries deleted since the vault was opened with where they were in the list, the last one on top
+    deleted: Vec<(usize, Password)>,
+    // entries in the trash, loaded when the trash is opened
+    trash: Vec<Trashed>,
+    trash_state: ListState,
+    folders: Vec<Folder>,
+    // folders whose subfolders the tree doesn't show
+    collapsed: HashSet<usize>,
+    // the folder selected in the tree, the list only shows what's in it. None shows every entry
+    folder: Option<usize>,
+    folder_state: ListState,
+    // name typed in the folder popup, and the folder it renames (None adds one to the selected folder)
+    folder_name: String,
+    renamed_folder: Option<usize>,
+    folder_error: Option<String>,
+    // where the entry goes in the move popup: 0 is the top level, the folders follow in tree order
+    move_state: ListState,
     edit_mode: bool,
-    edit_index: Option<usize>
+    // id of the entry the form edits
+    edit_id: Option<usize>
 }
 
 impl PassMng {
 
-    pub fn new(key: String) -> PassMng {
-        let db = match Database::new(key) {
-            Ok(db) => db,
-            Err(e) => {
-                if e.sqlite_error_code().unwrap() == ErrorCode::NotADatabase {
-                    println!("passphrase is not valid!");
-                    std::process::exit(1);
-                }else {
-                    println!("{}", e.to_string());
-                    std::process::exit(1);
-                }
-            }
-        };
-        let passwords = db.load();
-        PassMng {
-            db,
+    pub fn new(db: Database, vault_path: PathBuf, config: Config) -> Result<PassMng, DbError> {
+        let passwords = db.load()?;
+        let folders = db.load_folders()?;
+        let mut state = PassMng {
+            db: Some(db),
+            vaults: config.vaults().into_iter().collect(),
+            generator: config.generator.to_owned(),
+            clipboard: ClipboardTimer::new(config.clipboard_timeout),
+            last_activity: Instant::now(),
+            unlock_key: SecretString::default(),
+            unlock_error: None,
+            error: None,
+            config,
+            vault_path,
+            vault_state: ListState::default(),
+            vault_key: SecretString::default(),
+            vault_error: None,
+            rekey_fields: Default::default(),
+            rekey_field: 0,
+            rekey_result: None,
             mode: InputMode::Normal,
             list_state: ListState::default(),
             passwords,
             search_txt: String::new(),
-            search_list: vec![],
+            show_secrets: false,
+            revealed: HashSet::new(),
             new_title: String::new(),
             new_username: String::new(),
-            new_password: String::new(),
+            new_password: SecretString::default(),
+            peek: false,
+            new_urls: String::new(),
+            new_tags: String::new(),
+            new_notes: String::new(),
+            new_favorite: false,
+            new_totp: SecretString::default(),
+            form_error: None,
+            new_custom_fields: vec![],
+            custom_fields_state: ListState::default(),
+            custom_field_inputs: Default::default(),
+            custom_field_input: 0,
+            custom_field_index: None,
+            generated: Err(String::new()),
             edit_mode: false,
-            edit_index: None
-        }
+            audit: vec![],
+            audit_state: ListState::default(),
+            breaches: HashMap::new(),
+            history: vec![],
+            history_state: ListState::default(),
+            deleted: vec![],
+            trash: vec![],
+            trash_state: ListState::default(),
+            folders,
+            collapsed: HashSet::new(),
+            folder: None,
+            folder_state: ListState::default(),
+            folder_name: String::new(),
+            renamed_folder: None,
+            folder_error: None,
+            move_state: ListState::default(),
+            edit_id: None
+        };
+        state.check_breaches();
+        Ok(state)
+    }
+
+    fn db(&self) -> &Database {
+        self.db.as_ref().unwrap()
     }
 
     pub fn change_mode(&mut self, mode: InputMode) {
@@ -122,258 +378,1128 @@ impl PassMng {
         self.new_title.clear();
         self.new_username.clear();
         self.new_password.clear();
+        self.peek = false;
+        self.new_urls.clear();
+        self.new_tags.clear();
+        self.new_notes.clear();
+        self.new_favorite = false;
+        self.new_totp.clear();
+        self.form_error = None;
+        self.new_custom_fields.clear();
+        self.custom_fields_state.select(None);
+    }
+
+    // the text buffer behind the focused form field
+    pub fn form_field(&mut self) -> Option<&mut String> {
+        match self.mode {
+            InputMode::Title => Some(&mut self.new_title),
+            InputMode::Username => Some(&mut self.new_username),
+            InputMode::Url => Some(&mut self.new_urls),
+            InputMode::Tags => Some(&mut self.new_tags),
+            InputMode::Notes => Some(&mut self.new_notes),
+            _ => None
+        }
+    }
+
+    // same as form_field, for the fields that hold secrets
+    pub fn secret_form_field(&mut self) -> Option<&mut SecretString> {
+        match self.mode {
+            InputMode::Password => Some(&mut self.new_password),
+            InputMode::Totp => Some(&mut self.new_totp),
+            _ => None
+        }
+    }
+
+    pub fn next_field(&mut self) {
+        if let Some(i) = FORM_FIELDS.iter().position(|mode| *mode == self.mode) {
+            self.change_mode(FORM_FIELDS[(i + 1).min(FORM_FIELDS.len() - 1)]);
+        }
+    }
+
+    pub fn previous_field(&mut self) {
+        if let Some(i) = FORM_FIELDS.iter().position(|mode| *mode == self.mode) {
+            self.change_mode(FORM_FIELDS[i.saturating_sub(1)]);
+        }
+    }
+
+    // copies the form fields that Password::new doesn't take, on error the form has to be
+    // fixed before it can be submitted
+    fn fill_extra_fields(&mut self, password: &mut Password) -> Result<(), ()> {
+        password.totp = if self.new_totp.expose().trim().is_empty() {
+            None
+        }else {
+            match Totp::parse(self.new_totp.expose()) {
+                Ok(totp) => Some(totp),
+                Err(e) => {
+                    self.form_error = Some(e);
+                    self.change_mode(InputMode::Totp);
+                    return Err(());
+                }
+            }
+        };
+        password.urls = self.new_urls.split_whitespace().map(str::to_owned).collect();
+        password.tags = split_tags(&self.new_tags);
+        password.notes = self.new_notes.to_owned();
+        password.favorite = self.new_favorite;
+        password.custom_fields = self.new_custom_fields.to_owned();
+        Ok(())
+    }
+
+    pub fn move_custom_field_selection(&mut self, up: bool) {
+        step(&mut self.custom_fields_state, self.new_custom_fields.len(), up);
+    }
+
+    // opens the custom field popup, for the selected field or for a new one
+    pub fn start_custom_field(&mut self, edit_selected: bool) {
+        self.custom_field_index = None;
+        self.custom_field_inputs = Default::default();
+        if edit_selected {
+            match self.custom_fields_state.selected() {
+                Some(index) => {
+                    let field = &self.new_custom_fields[index];
+                    self.custom_field_inputs = [SecretString::from(field.name.as_str()), field.value.clone()];
+                    self.custom_field_index = Some(index);
+                }
+                None => return
+            }
+        }
+        self.custom_field_input = 0;
+        self.peek = false;
+        self.change_mode(InputMode::CustomField);
+    }
+
+    pub fn save_custom_field(&mut self) {
+        let [name, value] = std::mem::take(&mut self.custom_field_inputs);
+        let name = name.expose().to_owned();
+        if !name.is_empty() {
+            match self.custom_field_index {
+                Some(index) => {
+                    self.new_custom_fields[index].name = name;
+                    self.new_custom_fields[index].value = value;
+                }
+                None => {
+                    self.new_custom_fields.push(CustomField { name, value, hidden: false });
+                    self.custom_fields_state.select(Some(self.new_custom_fields.len() - 1));
+                }
+            }
+        }
+        self.peek = false;
+        self.change_mode(InputMode::CustomFields);
+    }
+
+    pub fn delete_custom_field(&mut self) {
+        if let Some(index) = self.custom_fields_state.selected() {
+            self.new_custom_fields.remove(index);
+            self.custom_fields_state.select(if self.new_custom_fields.is_empty() { None } else { Some(0) });
+        }
+    }
+
+    pub fn toggle_custom_field_hidden(&mut self) {
+        if let Some(index) = self.custom_fields_state.selected() {
+            self.new_custom_fields[index].hidden = !self.new_custom_fields[index].hidden;
+        }
     }
 
     pub fn insert(&mut self) {
-        let password = Password::new(
+        let mut password = Password::new(
             self.new_title.to_owned(),
             self.new_username.to_owned(),
-            self.new_password.to_owned()
+            self.new_password.clone()
         );
-        self.db.insert(&password);
+        password.folder = self.folder;
+        if self.fill_extra_fields(&mut password).is_err() {
+            return;
+        }
+        let result = self.db().insert(&password);
+        match self.report(result) {
+            Some(id) => password.id = id,
+            None => return
+        }
         self.passwords.push(password);
+        self.check_breaches();
         self.clear_fields();
         self.change_mode(InputMode::Normal);
     }
 
     pub fn start_edit_mode(&mut self) {
-        if let Some(index) = self.list_state.selected() {
+        if let Some(index) = self.selected_index() {
             let password = &self.passwords[index];
             self.new_title = password.title.to_owned();
             self.new_username = password.username.to_owned();
-            self.new_password = password.password.to_owned();
+            self.new_password = password.password.clone();
+            self.new_urls = password.urls.join(" ");
+            self.new_tags = password.tags.join(", ");
+            self.new_notes = password.notes.to_owned();
+            self.new_favorite = password.favorite;
+            self.new_totp = password.totp.as_ref().map(Totp::to_uri).unwrap_or_default();
+            self.new_custom_fields = password.custom_fields.to_owned();
             self.edit_mode = true;
-            self.edit_index = Some(index);
+            self.edit_id = Some(password.id);
             self.change_mode(InputMode::Title);
         }
     }
 
     pub fn edit(&mut self) {
-        let index = self.edit_index.unwrap();
-        let id = self.passwords[index].id;
-        let password = Password::new(
-            self.new_title.to_owned(),
-            self.new_username.to_owned(),
-            self.new_password.to_owned()
-        );
-        self.db.update(id, &password);
+        // the entry may have been deleted since the form was opened
+        let Some((index, id)) = self.edit_id
+            .and_then(|id| Some((self.passwords.iter().position(|password| password.id == id)?, id))) else {
+            self.error = Some("The entry being edited no longer exists".to_owned());
+            self.close_form();
+            return;
+        };
+        let mut password = self.passwords[index].clone();
+        password.title = self.new_title.to_owned();
+        password.username = self.new_username.to_owned();
+        password.password = self.new_password.clone();
+        password.modified_at = chrono::Utc::now().timestamp();
+        if self.fill_extra_fields(&mut password).is_err() {
+            return;
+        }
+        let result = self.db().update(id, &password);
+        if self.report(result).is_none() {
+            return;
+        }
         self.passwords[index] = password;
+        self.check_breaches();
         self.clear_fields();
         self.end_edit_mode();
         self.change_mode(InputMode::List);
     }
 
     pub fn end_edit_mode(&mut self) {
-        if self.edit_mode {
-            self.edit_mode = false;
-            self.edit_index = None;
-        }
+        self.edit_mode = false;
+        self.edit_id = None;
+    }
+
+    // leaves the form without saving
+    pub fn close_form(&mut self) {
+        self.clear_fields();
+        self.end_edit_mode();
+        self.change_mode(InputMode::Normal);
     }
 
     pub fn check_delete(&mut self) {
-        if self.list_state.selected().is_some() {
+        if self.selected_index().is_some() {
             self.change_mode(InputMode::Delete);
         }
     }
 
+    // moves the entry to the trash
     pub fn delete(&mut self) {
-        if let Some(index) = self.list_state.selected() {
+        if let Some(index) = self.selected_index() {
             let id = self.passwords[index].id;
-            self.passwords.remove(index);
-            self.db.delete(id);
-            if index > 0 {
-                self.list_state.select(Some(0));
-            }else {
-                self.list_state.select(None);
+            let result = self.db().delete(id, chrono::Utc::now().timestamp());
+            if self.report(result).is_none() {
+                self.change_mode(InputMode::List);
+                return;
             }
+            let password = self.passwords.remove(index);
+            self.deleted.push((index, password));
+            // stay at the same spot in the list, or on the last entry when it was the last one
+            let len = self.visible().len();
+            let selected = self.list_state.selected().filter(|_| len > 0).map(|v| v.min(len - 1));
+            self.list_state.select(selected);
             self.change_mode(InputMode::List);
         }
     }
 
+    pub fn undo_delete(&mut self) {
+        let Some((index, password)) = self.deleted.pop() else {
+            return;
+        };
+        let result = self.db().restore(password.id);
+        if self.report(result).is_none() {
+            return;
+        }
+        let id = password.id;
+        self.passwords.insert(index.min(self.passwords.len()), password);
+        self.check_breaches();
+        let selected = self.visible().iter().position(|password| password.id == id);
+        self.list_state.select(selected);
+    }
+
+    // the entries in the order the list shows them: all of them, or what the search found,
+    // limited to the selected folder and its subfolders
+    pub fn visible(&self) -> Vec<&Password> {
+        let shown = self.folder.map(|id| folders::subtree(&self.folders, id));
+        let in_folder = |password: &&Password| match &shown {
+            Some(ids) => password.folder.map(|id| ids.contains(&id)).unwrap_or(false),
+            None => true
+        };
+        if self.search_txt.trim().is_empty() {
+            self.passwords.iter().filter(in_folder).collect()
+        }else {
+            search::rank(&Query::parse(&self.search_txt), &self.passwords).into_iter().filter(in_folder).collect()
+        }
+    }
+
+    // the position in `passwords` of the entry selected in the list. The list may be filtered
+    // and ordered differently, so the selection is looked up by the id of the entry it shows
+    fn selected_index(&self) -> Option<usize> {
+        let id = self.visible().get(self.list_state.selected()?)?.id;
+        self.passwords.iter().position(|password| password.id == id)
+    }
+
+    // the results change with every key typed, so start over at the top
     pub fn search(&mut self) {
-        self.search_list = self.passwords.clone().into_iter()
-            .filter(|item| item.title.starts_with(&self.search_txt.to_owned()))
-            .collect();
+        self.list_state.select(None);
     }
 
     pub fn move_up(&mut self) {
-        let selected = match self.list_state.selected() {
-            Some(v) => {
-                if v == 0 {
-                    Some(v)
-                }else {
-                    Some(v - 1)
-                }
-            }
-            None => {
-                Some(0)
-            }
-        };
-        self.list_state.select(selected);
+        let len = self.visible().len();
+        step(&mut self.list_state, len, true);
     }
 
     pub fn move_down(&mut self) {
-        let selected = match self.list_state.selected() {
-            Some(v) => {
-                if v == self.passwords.len() - 1 {
-                    Some(v)
-                }else {
-                    Some(v + 1)
-                }
+        let len = self.visible().len();
+        step(&mut self.list_state, len, false);
+    }
+
+    pub fn copy_username(&mut self) {
+        if let Some(index) = self.selected_index() {
+            let username = SecretString::from(self.passwords[index].username.as_str());
+            self.copy(index, username);
+        }
+    }
+
+    pub fn copy_password(&mut self) {
+        if let Some(index) = self.selected_index() {
+            let password = self.passwords[index].password.clone();
+            self.copy(index, password);
+        }
+    }
+
+    pub fn is_revealed(&self, item: &Password) -> bool {
+        self.show_secrets || self.revealed.contains(&item.id)
+    }
+
+    pub fn toggle_reveal(&mut self) {
+        if let Some(index) = self.selected_index() {
+            let id = self.passwords[index].id;
+            if !self.revealed.remove(&id) {
+                self.revealed.insert(id);
+            }
+        }
+    }
+
+    pub fn open_generator(&mut self) {
+        self.generated = self.generator.generate();
+        self.change_mode(InputMode::Generator);
+    }
+
+    // applies a change to the generator settings and generates a new password with them
+    pub fn update_generator(&mut self, change: impl FnOnce(&mut GeneratorSettings)) {
+        change(&mut self.generator);
+        self.generated = self.generator.generate();
+    }
+
+    pub fn use_generated(&mut self) {
+        if let Ok(generated) = &self.generated {
+            self.new_password = generated.password.clone();
+            self.change_mode(InputMode::Password);
+        }
+    }
+
+    pub fn copy_totp(&mut self) {
+        if let Some(index) = self.selected_index() {
+            if let Some(totp) = &self.passwords[index].totp {
+                self.copy(index, SecretString::new(totp.now().0));
             }
-            None => {
-                Some(0)
+        }
+    }
+
+    pub fn copy_custom_field(&mut self, number: usize) {
+        if let Some(index) = self.selected_index() {
+            if let Some(field) = self.passwords[index].custom_fields.get(number) {
+                self.copy(index, field.value.clone());
             }
+        }
+    }
+
+    fn mark_used(&mut self, index: usize) {
+        let now = chrono::Utc::now().timestamp();
+        self.passwords[index].last_used_at = Some(now);
+        let result = self.db().mark_used(self.passwords[index].id, now);
+        self.report(result);
+    }
+
+    pub fn vault_name(&self) -> String {
+        match self.vaults.iter().find(|(_, path)| *path == self.vault_path) {
+            Some((name, _)) => name.to_owned(),
+            None => self.vault_path.display().to_string()
+        }
+    }
+
+    pub fn open_audit(&mut self) {
+        self.audit = audit::audit(&self.passwords, &self.config.audit, &self.breaches, chrono::Utc::now().timestamp());
+        self.audit_state.select(if self.audit.is_empty() { None } else { Some(0) });
+        self.change_mode(InputMode::Audit);
+    }
+
+    pub fn move_audit_selection(&mut self, up: bool) {
+        step(&mut self.audit_state, self.audit.len(), up);
+    }
+
+    // selects the entry of the finding in the whole, unsearched list of every folder
+    pub fn jump_to_finding(&mut self) {
+        let Some(finding) = self.audit_state.selected().and_then(|i| self.audit.get(i)) else {
+            return;
         };
-        self.list_state.select(selected);
+        let index = self.passwords.iter().position(|password| password.id == finding.id);
+        self.search_txt.clear();
+        self.folder = None;
+        self.list_state.select(index);
+        self.change_mode(InputMode::List);
     }
 
-    pub fn copy_username(&self) {
-        if let Some(index) = self.list_state.selected() {
-            let username = self.passwords[index].username.to_owned();
-            PassMng::copy(username);
+    pub fn open_history(&mut self) {
+        let Some(index) = self.selected_index() else {
+            return;
+        };
+        let result = self.db().history(self.passwords[index].id);
+        if let Some(history) = self.report(result) {
+            self.history = history;
+            self.history_state.select(if self.history.is_empty() { None } else { Some(0) });
+            self.change_mode(InputMode::History);
         }
     }
 
-    pub fn copy_password(&self) {
-        if let Some(index) = self.list_state.selected() {
-            let password = self.passwords[index].password.to_owned();
-            PassMng::copy(password);
+    pub fn close_history(&mut self) {
+        self.history.clear();
+        self.change_mode(InputMode::List);
+    }
+
+    pub fn move_history_selection(&mut self, up: bool) {
+        step(&mut self.history_state, self.history.len(), up);
+    }
+
+    pub fn copy_old_password(&mut self) {
+        let (Some(index), Some(selected)) = (self.selected_index(), self.history_state.selected()) else {
+            return;
+        };
+        let password = self.history[selected].password.clone();
+        self.copy(index, password);
+    }
+
+    // makes the selected old password the current one again, the current one goes to the history
+    pub fn restore_old_password(&mut self) {
+        let (Some(index), Some(selected)) = (self.selected_index(), self.history_state.selected()) else {
+            return;
+        };
+        let mut password = self.passwords[index].clone();
+        password.password = self.history[selected].password.clone();
+        password.modified_at = chrono::Utc::now().timestamp();
+        let result = self.db().update(password.id, &password);
+        if self.report(result).is_none() {
+            return;
         }
+        self.passwords[index] = password;
+        self.check_breaches();
+        self.close_history();
     }
 
-    fn copy(content: String) {
-        let mut clipboard = Clipboard::new().unwrap();
-        clipboard.set_text(content).unwrap();
+    pub fn open_trash(&mut self) {
+        let result = self.db().load_trash();
+        if let Some(trash) = self.report(result) {
+            self.trash = trash;
+            self.trash_state.select(if self.trash.is_empty() { None } else { Some(0) });
+            self.change_mode(InputMode::Trash);
+        }
     }
 
-}
+    pub fn close_trash(&mut self) {
+        self.trash.clear();
+        self.change_mode(InputMode::Normal);
+    }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let passphrase = rpassword::prompt_password("Enter Passphrase: ").unwrap();
-    let mut state = PassMng::new(passphrase);
-    enable_raw_mode()?;
-    execute!(
-        std::io::stdout(),
-        EnterAlternateScreen,
-        EnableMouseCapture
-    )?;
-    let backend = CrosstermBackend::new(std::io::stdout());
-    let mut terminal = Terminal::new(backend)?;
+    pub fn move_trash_selection(&mut self, up: bool) {
+        step(&mut self.trash_state, self.trash.len(), up);
+    }
 
-    let result = run_app(&mut terminal, &mut state);
+    pub fn restore_from_trash(&mut self) {
+        let Some(selected) = self.trash_state.selected() else {
+            return;
+        };
+        let result = self.db().restore(self.trash[selected].password.id);
+        if self.report(result).is_none() {
+            return;
+        }
+        let password = self.remove_from_trash(selected);
+        self.passwords.push(password);
+        self.check_breaches();
+    }
 
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
+    pub fn check_purge(&mut self) {
+        if self.trash_state.selected().is_some() {
+            self.change_mode(InputMode::Purge);
+        }
+    }
 
-    if let Err(e) = result {
-        println!("{}", e.to_string());
+    pub fn purge(&mut self) {
+        let Some(selected) = self.trash_state.selected() else {
+            return;
+        };
+        let result = self.db().purge(self.trash[selected].password.id);
+        if self.report(result).is_some() {
+            self.remove_from_trash(selected);
+        }
+        self.change_mode(InputMode::Trash);
     }
 
-    Ok(())
-}
+    // takes the entry out of the trash list, and out of reach of undo
+    fn remove_from_trash(&mut self, selected: usize) -> Password {
+        let password = self.trash.remove(selected).password;
+        self.deleted.retain(|(_, deleted)| deleted.id != password.id);
+        let len = self.trash.len();
+        self.trash_state.select(if len == 0 { None } else { Some(selected.min(len - 1)) });
+        password
+    }
 
-fn run_app<B: Backend>(terminal: &mut Terminal<B>, state: &mut PassMng) -> Result<(), std::io::Error> {
-    loop {
-        terminal.draw(|f| ui(f, state))?;
+    // the rows of the folder tree, "All" at 0 comes before them
+    pub fn folder_rows(&self) -> Vec<folders::Row> {
+        folders::rows(&self.folders, &self.collapsed)
+    }
 
-        if let Key(key) = event::read()? {
+    pub fn move_folder_selection(&mut self, up: bool) {
+        let rows = self.folder_rows();
+        let current = self.folder.and_then(|id| rows.iter().position(|row| row.id == id)).map(|i| i + 1).unwrap_or(0);
+        let selected = if up {
+            current.saturating_sub(1)
+        }else {
+            (current + 1).min(rows.len())
+        };
+        self.folder = selected.checked_sub(1).map(|i| rows[i].id);
+        self.list_state.select(None);
+    }
 
-            match state.mode {
-                InputMode::Normal => {
-                    match key.code {
-                        KeyCode::Char('q') => {
-                            return Ok(());
-                        }
-                        KeyCode::Char('s') => {
-                            state.change_mode(InputMode::Search);
-                        }
-                        KeyCode::Char('l') => {
-                            state.change_mode(InputMode::List);
-                        }
-                        KeyCode::Insert => {
-                            state.change_mode(InputMode::Title);
-                        }
-                        _ => {}
-                    }
-                }
+    pub fn fold_folder(&mut self, collapse: bool) {
+        if let Some(id) = self.folder {
+            if collapse {
+                self.collapsed.insert(id);
+            }else {
+                self.collapsed.remove(&id);
+            }
+        }
+    }
 
-                InputMode::Title => {
-                    match key.code {
-                        KeyCode::Esc => {
-                            state.clear_fields();
-                            state.change_mode(InputMode::Normal);
-                        }
-                        KeyCode::Char(c) => {
-                            state.new_title.push(c);
-                        }
-                        KeyCode::Backspace => {
-                            state.new_title.pop();
-                        }
-                        KeyCode::Tab => {
-                            state.change_mode(InputMode::Username);
-                        }
-                        _ => {}
+    // opens the name popup, to rename the selected folder or to add one in it
+    pub fn start_folder_name(&mut self, rename: bool) {
+        self.renamed_folder = None;
+        self.folder_name.clear();
+        if rename {
+            let Some(id) = self.folder else {
+                return;
+            };
+            self.renamed_folder = Some(id);
+            self.folder_name = self.folders.iter().find(|folder| folder.id == id).unwrap().name.to_owned();
+        }
+        self.folder_error = None;
+        self.change_mode(InputMode::FolderName);
+    }
+
+    pub fn save_folder_name(&mut self) {
+        let name = self.folder_name.trim().to_owned();
+        let renamed = self.renamed_folder.and_then(|id| self.folders.iter().position(|folder| folder.id == id));
+        let parent = match renamed {
+            Some(i) => self.folders[i].parent,
+            None => self.folder
+        };
+        let unchanged = renamed.map(|i| self.folders[i].name == name).unwrap_or(false);
+        if !unchanged {
+            if let Err(e) = folders::check_name(&self.folders, parent, &name) {
+                self.folder_error = Some(e);
+                return;
+            }
+            match renamed {
+                Some(i) => {
+                    let result = self.db().rename_folder(self.folders[i].id, &name);
+                    if self.report(result).is_none() {
+                        return;
                     }
+                    self.folders[i].name = name;
                 }
-
-                InputMode::Username => {
-                    match key.code {
-                        KeyCode::Esc => {
-                            state.clear_fields();
-                            state.change_mode(InputMode::Normal);
-                        }
-                        KeyCode::Char(c) => {
-                            state.new_username.push(c);
-                        }
-                        KeyCode::Backspace => {
-                            state.new_username.pop();
-                        }
-                        KeyCode::Tab => {
-                            state.change_mode(InputMode::Password);
-                        }
-                        KeyCode::BackTab => {
-                            state.change_mode(InputMode::Title);
-                        }
-                        _ => {}
+                None => {
+                    let result = self.db().insert_folder(parent, &name);
+                    let Some(id) = self.report(result) else {
+                        return;
+                    };
+                    self.folders.push(Folder { id, parent, name });
+                    // so the new folder shows up
+                    if let Some(parent) = parent {
+                        self.collapsed.remove(&parent);
                     }
                 }
+            }
+        }
+        self.folder_name.clear();
+        self.change_mode(InputMode::Folders);
+    }
 
-                InputMode::Password => {
-                    match key.code {
-                        KeyCode::Esc => {
-                            state.clear_fields();
-                            state.change_mode(InputMode::Normal);
-                        }
+    pub fn check_delete_folder(&mut self) {
+        if self.folder.is_some() {
+            self.change_mode(InputMode::DeleteFolder);
+        }
+    }
+
+    // what was in the folder moves up to its parent, which is selected next
+    pub fn delete_folder(&mut self) {
+        let id = self.folder.unwrap();
+        let parent = self.folders.iter().find(|folder| folder.id == id).unwrap().parent;
+        let result = self.db().delete_folder(id);
+        if self.report(result).is_some() {
+            self.folders.retain(|folder| folder.id != id);
+            self.folders.iter_mut().filter(|folder| folder.parent == Some(id)).for_each(|folder| folder.parent = parent);
+            self.passwords.iter_mut().filter(|password| password.folder == Some(id)).for_each(|password| password.folder = parent);
+            self.collapsed.remove(&id);
+            self.folder = parent;
+            self.list_state.select(None);
+        }
+        self.change_mode(InputMode::Folders);
+    }
+
+    pub fn open_move_entry(&mut self) {
+        if let Some(index) = self.selected_index() {
+            let rows = folders::rows(&self.folders, &HashSet::new());
+            let current = self.passwords[index].folder.and_then(|id| rows.iter().position(|row| row.id == id));
+            self.move_state.select(Some(current.map(|i| i + 1).unwrap_or(0)));
+            self.change_mode(InputMode::MoveEntry);
+        }
+    }
+
+    pub fn move_move_selection(&mut self, up: bool) {
+        step(&mut self.move_state, self.folders.len() + 1, up);
+    }
+
+    pub fn move_entry(&mut self) {
+        let (Some(index), Some(selected)) = (self.selected_index(), self.move_state.selected()) else {
+            return;
+        };
+        let rows = folders::rows(&self.folders, &HashSet::new());
+        let folder = selected.checked_sub(1).map(|i| rows[i].id);
+        let result = self.db().move_to_folder(self.passwords[index].id, folder);
+        if self.report(result).is_some() {
+            self.passwords[index].folder = folder;
+            // the entry may have left the folder the list shows
+            let len = self.visible().len();
+            let selected = self.list_state.selected().filter(|_| len > 0).map(|v| v.min(len - 1));
+            self.list_state.select(selected);
+        }
+        self.change_mode(InputMode::List);
+    }
+
+    // looks every password up in the breach list, when there is one
+    pub fn check_breaches(&mut self) {
+        let Some(list) = &self.config.audit.breach_list else {
+            return;
+        };
+        let result = hibp::check(&self.passwords, list)
+            .map_err(|e| format!("Can't check {} for breached passwords: {}", list.display(), e));
+        self.breaches = self.report(result).unwrap_or_default();
+    }
+
+    pub fn open_vault_switcher(&mut self) {
+        let current = self.vaults.iter().position(|(_, path)| *path == self.vault_path);
+        self.vault_state.select(current.or(Some(0)));
+        self.vault_error = None;
+        self.change_mode(InputMode::Vaults);
+    }
+
+    pub fn move_vault_selection(&mut self, up: bool) {
+        step(&mut self.vault_state, self.vaults.len(), up);
+    }
+
+    pub fn switch_vault(&mut self) {
+        let index = self.vault_state.selected().unwrap();
+        let path = self.vaults[index].1.to_owned();
+        let key = std::mem::take(&mut self.vault_key);
+        let opened = open(&path, &key, &self.config).and_then(load);
+        match opened {
+            Ok((db, passwords, folders)) => {
+                self.passwords = passwords;
+                self.folders = folders;
+                self.db = Some(db);
+                self.vault_path = path;
+                self.deleted.clear();
+                self.folder = None;
+                self.collapsed.clear();
+                self.show_secrets = false;
+                self.revealed.clear();
+                self.clear_fields();
+                self.end_edit_mode();
+                self.check_breaches();
+                self.list_state.select(None);
+                self.search_txt.clear();
+                self.change_mode(InputMode::Normal);
+            }
+            Err(e) => {
+                self.vault_error = Some(e.to_string());
+                self.change_mode(InputMode::Vaults);
+            }
+        }
+    }
+
+    pub fn open_change_key(&mut self) {
+        self.rekey_field = 0;
+        self.rekey_result = None;
+        self.change_mode(InputMode::ChangeKey);
+    }
+
+    pub fn close_change_key(&mut self) {
+        self.rekey_fields.iter_mut().for_each(SecretString::clear);
+        self.change_mode(InputMode::Normal);
+    }
+
+    pub fn change_key(&mut self) {
+        let [old_key, new_key, confirm] = std::mem::take(&mut self.rekey_fields);
+        self.rekey_field = 0;
+        self.rekey_result = if new_key.is_empty() {
+            Some(Err("new passphrase can't be empty".to_owned()))
+        }else if new_key != confirm {
+            Some(Err("passphrases do not match".to_owned()))
+        }else {
+            match self.db.as_mut().unwrap().change_key(&old_key, &new_key) {
+                Ok(backup) => Some(Ok(format!("Passphrase changed, backup: {}", backup.display()))),
+                Err(e) => Some(Err(e.to_string()))
+            }
+        };
+    }
+
+    pub fn lock_if_idle(&mut self) {
+        let timeout = self.config.lock_timeout;
+        if timeout > 0 && self.mode != InputMode::Locked && self.last_activity.elapsed() >= Duration::from_secs(timeout) {
+            self.lock();
+        }
+    }
+
+    // closes the database and forgets everything that was decrypted or typed in
+    pub fn lock(&mut self) {
+        self.db = None;
+        self.passwords.clear();
+        self.search_txt.clear();
+        self.show_secrets = false;
+        self.revealed.clear();
+        self.list_state.select(None);
+        self.clear_fields();
+        self.end_edit_mode();
+        self.custom_field_inputs = Default::default();
+        self.generated = Err(String::new());
+        self.vault_key.clear();
+        self.rekey_fields = Default::default();
+        self.rekey_result = None;
+        self.clipboard.clear();
+        self.unlock_error = None;
+        // popups left open would come back on top of the unlocked vault
+        self.error = None;
+        self.vault_error = None;
+        self.custom_field_index = None;
+        self.audit.clear();
+        self.audit_state.select(None);
+        self.breaches.clear();
+        self.history.clear();
+        self.history_state.select(None);
+        self.deleted.clear();
+        self.trash.clear();
+        self.trash_state.select(None);
+        self.folders.clear();
+        self.folder = None;
+        self.folder_state.select(None);
+        self.folder_name.clear();
+        self.renamed_folder = None;
+        self.folder_error = None;
+        self.move_state.select(None);
+        self.change_mode(InputMode::Locked);
+    }
+
+    pub fn unlock(&mut self) {
+        let key = std::mem::take(&mut self.unlock_key);
+        let opened = open(&self.vault_path, &key, &self.config).and_then(load);
+        match opened {
+            Ok((db, passwords, folders)) => {
+                self.passwords = passwords;
+                self.folders = folders;
+                self.db = Some(db);
+                self.check_breaches();
+                self.change_mode(InputMode::Normal);
+            }
+            Err(e) => {
+                self.unlock_error = Some(e.to_string());
+            }
+        }
+    }
+
+    // copies something of the entry at `index` and records that the entry was used
+    fn copy(&mut self, index: usize, content: SecretString) {
+        let result = self.clipboard.copy(content);
+        if self.report(result).is_some() {
+            self.mark_used(index);
+        }
+    }
+
+    // puts the error up in the popup, the value comes back when there wasn't one
+    fn report<T, E: std::fmt::Display>(&mut self, result: Result<T, E>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(e) => {
+                self.error = Some(e.to_string());
+                None
+            }
+        }
+    }
+
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+    let mut config = Config::load();
+    let vault = config.resolve_vault(cli.vault.as_deref());
+    if let Some(command) = cli.command {
+        if let Err(e) = cli::run(command, &mut config, &vault) {
+            println!("{}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // the first-run setup asks for everything, with the passphrase given there's nothing to ask
+    let interactive = std::env::var_os("PASSMNG_PASSPHRASE").is_none();
+    let (db, _) = unlock(&vault, &config, interactive);
+    let mut state = match PassMng::new(db, vault, config) {
+        Ok(state) => state,
+        Err(e) => {
+            println!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // a panic would otherwise leave the terminal in raw mode, with the panic message lost
+    // on the alternate screen
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = restore_terminal();
+        default_hook(info);
+    }));
+
+    enable_raw_mode()?;
+    execute!(
+        std::io::stdout(),
+        EnterAlternateScreen,
+        EnableMouseCapture
+    )?;
+    let backend = CrosstermBackend::new(std::io::stdout());
+    let mut terminal = Terminal::new(backend)?;
+
+    let result = run_app(&mut terminal, &mut state);
+    // the timer can't fire anymore once we're gone
+    state.clipboard.clear();
+
+    restore_terminal()?;
+    terminal.show_cursor()?;
+
+    if let Err(e) = result {
+        println!("{}", e);
+    }
+
+    Ok(())
+}
+
+fn restore_terminal() -> Result<(), std::io::Error> {
+    disable_raw_mode()?;
+    execute!(
+        std::io::stdout(),
+        LeaveAlternateScreen,
+        DisableMouseCapture
+    )
+}
+
+// scripts can't type into the prompt, so let them hand the passphrase over through the environment
+fn read_passphrase() -> SecretString {
+    match std::env::var("PASSMNG_PASSPHRASE") {
+        Ok(passphrase) => SecretString::new(passphrase),
+        Err(_) => SecretString::new(rpassword::prompt_password("Enter Passphrase: ").unwrap())
+    }
+}
+
+// opens an existing vault with the settings it has in the config
+fn open(vault: &Path, key: &SecretString, config: &Config) -> Result<Database, DbError> {
+    let mut db = Database::new(vault, key)?;
+    db.keep_backups(config.backups.clone());
+    db.keep_history(config.password_history);
+    if config.trash_days > 0 {
+        db.purge_trash(chrono::Utc::now().timestamp() - config.trash_days as i64 * 24 * 60 * 60)?;
+    }
+    Ok(db)
+}
+
+// reads everything the TUI shows out of the vault
+fn load(db: Database) -> Result<(Database, Vec<Password>, Vec<Folder>), DbError> {
+    let passwords = db.load()?;
+    let folders = db.load_folders()?;
+    Ok((db, passwords, folders))
+}
+
+// opens the vault, or runs the first-run setup when there is no vault yet and `create` is set.
+// Otherwise a mistyped path or profile name would leave a stray empty vault behind
+fn unlock(vault: &Path, config: &Config, create: bool) -> (Database, SecretString) {
+    if !vault.exists() {
+        if !create {
+            println!("{}: {} Create it with `passmng init`.", vault.display(), DbError::NotFound);
+            std::process::exit(1);
+        }
+        return match setup::create_vault(vault) {
+            Ok((mut db, key)) => {
+                db.keep_backups(config.backups.clone());
+                db.keep_history(config.password_history);
+                (db, key)
+            }
+            Err(e) => {
+                println!("{}", e);
+                std::process::exit(1);
+            }
+        };
+    }
+
+    let key = read_passphrase();
+    match open(vault, &key, config) {
+        Ok(db) => (db, key),
+        Err(e) => {
+            println!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run_app<B: Backend>(terminal: &mut Terminal<B>, state: &mut PassMng) -> Result<(), std::io::Error> {
+    loop {
+        state.clipboard.tick();
+        state.lock_if_idle();
+        terminal.draw(|f| ui(f, state))?;
+
+        // wake up now and then even without input, so the TOTP countdown keeps moving
+        // and the idle lock can kick in
+        if !event::poll(Duration::from_millis(500))? {
+            continue;
+        }
+        if let Key(key) = event::read()? {
+            state.last_activity = Instant::now();
+            if state.error.take().is_some() {
+                continue;
+            }
+
+            match state.mode {
+                InputMode::Normal => {
+                    match key.code {
+                        KeyCode::Char('q') => {
+                            return Ok(());
+                        }
+                        KeyCode::Char('s') => {
+                            state.change_mode(InputMode::Search);
+                        }
+                        KeyCode::Char('l') => {
+                            state.change_mode(InputMode::List);
+                        }
+                        KeyCode::Char('v') => {
+                            state.open_vault_switcher();
+                        }
+                        KeyCode::Char('k') => {
+                            state.open_change_key();
+                        }
+                        KeyCode::Char('x') => {
+                            state.lock();
+                        }
+                        KeyCode::Char('a') => {
+                            state.open_audit();
+                        }
+                        KeyCode::Char('t') => {
+                            state.open_trash();
+                        }
+                        KeyCode::Char('f') => {
+                            state.change_mode(InputMode::Folders);
+                        }
+                        KeyCode::Char('R') => {
+                            state.show_secrets = !state.show_secrets;
+                        }
+                        KeyCode::Insert => {
+                            state.change_mode(InputMode::Title);
+                        }
+                        _ => {}
+                    }
+                }
+
+                InputMode::Title | InputMode::Username | InputMode::Password
+                | InputMode::Url | InputMode::Tags | InputMode::Totp | InputMode::Notes => {
+                    match key.code {
+                        KeyCode::Esc => {
+                            state.close_form();
+                        }
+                        KeyCode::Char('g') if state.mode == InputMode::Password && key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            state.open_generator();
+                        }
+                        KeyCode::Char('r') if matches!(state.mode, InputMode::Password | InputMode::Totp) && key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            state.peek = !state.peek;
+                        }
                         KeyCode::Char(c) => {
-                            state.new_password.push(c);
+                            if let Some(field) = state.secret_form_field() {
+                                field.push(c);
+                            }else {
+                                state.form_field().unwrap().push(c);
+                            }
                         }
                         KeyCode::Backspace => {
-                            state.new_password.pop();
+                            if let Some(field) = state.secret_form_field() {
+                                field.pop();
+                            }else {
+                                state.form_field().unwrap().pop();
+                            }
+                        }
+                        KeyCode::Enter if state.mode == InputMode::Notes => {
+                            state.new_notes.push('\n');
                         }
                         KeyCode::Tab => {
-                            state.change_mode(InputMode::Submit);
+                            state.next_field();
                         }
                         KeyCode::BackTab => {
-                            state.change_mode(InputMode::Username);
+                            state.previous_field();
                         }
                         _ => {}
                     }
                 }
 
-                InputMode::Submit => {
+                InputMode::CustomFields => {
                     match key.code {
                         KeyCode::Esc => {
-                            state.clear_fields();
-                            state.change_mode(InputMode::Normal);
+                            state.close_form();
+                        }
+                        KeyCode::Up => {
+                            state.move_custom_field_selection(true);
+                        }
+                        KeyCode::Down => {
+                            state.move_custom_field_selection(false);
+                        }
+                        KeyCode::Char('a') => {
+                            state.start_custom_field(false);
+                        }
+                        KeyCode::Char('e') | KeyCode::Enter => {
+                            state.start_custom_field(true);
+                        }
+                        KeyCode::Char('d') => {
+                            state.delete_custom_field();
+                        }
+                        KeyCode::Char('h') => {
+                            state.toggle_custom_field_hidden();
+                        }
+                        KeyCode::Tab => {
+                            state.next_field();
                         }
                         KeyCode::BackTab => {
+                            state.previous_field();
+                        }
+                        _ => {}
+                    }
+                }
+
+                InputMode::CustomField => {
+                    match key.code {
+                        KeyCode::Esc => {
+                            state.custom_field_inputs = Default::default();
+                            state.peek = false;
+                            state.change_mode(InputMode::CustomFields);
+                        }
+                        KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            state.peek = !state.peek;
+                        }
+                        KeyCode::Char(c) => {
+                            state.custom_field_inputs[state.custom_field_input].push(c);
+                        }
+                        KeyCode::Backspace => {
+                            state.custom_field_inputs[state.custom_field_input].pop();
+                        }
+                        KeyCode::Tab | KeyCode::BackTab => {
+                            state.custom_field_input = 1 - state.custom_field_input;
+                        }
+                        KeyCode::Enter => {
+                            state.save_custom_field();
+                        }
+                        _ => {}
+                    }
+                }
+
+                InputMode::Generator => {
+                    match key.code {
+                        KeyCode::Esc => {
                             state.change_mode(InputMode::Password);
                         }
+                        KeyCode::Enter => {
+                            state.use_generated();
+                        }
+                        KeyCode::Left => {
+                            state.update_generator(|g| match g.mode {
+                                GeneratorMode::Characters => g.length = g.length.saturating_sub(1),
+                                GeneratorMode::Passphrase => g.words = g.words.saturating_sub(1)
+                            });
+                        }
+                        KeyCode::Right => {
+                            state.update_generator(|g| match g.mode {
+                                GeneratorMode::Characters => g.length += 1,
+                                GeneratorMode::Passphrase => g.words += 1
+                            });
+                        }
+                        KeyCode::Char('w') => {
+                            state.update_generator(|g| g.mode = match g.mode {
+                                GeneratorMode::Characters => GeneratorMode::Passphrase,
+                                GeneratorMode::Passphrase => GeneratorMode::Characters
+                            });
+                        }
+                        KeyCode::Char('l') => {
+                            state.update_generator(|g| g.lowercase = !g.lowercase);
+                        }
+                        KeyCode::Char('u') => {
+                            state.update_generator(|g| g.uppercase = !g.uppercase);
+                        }
+                        KeyCode::Char('d') => {
+                            state.update_generator(|g| g.digits = !g.digits);
+                        }
+                        KeyCode::Char('s') => {
+                            state.update_generator(|g| g.symbols = !g.symbols);
+                        }
+                        KeyCode::Char('a') => {
+                            state.update_generator(|g| g.exclude_ambiguous = !g.exclude_ambiguous);
+                        }
+                        KeyCode::Char('m') => {
+                            state.update_generator(|g| g.must_include = !g.must_include);
+                        }
+                        KeyCode::Char('r') => {
+                            state.update_generator(|_| {});
+                        }
+                        _ => {}
+                    }
+                }
+
+                InputMode::Favorite => {
+                    match key.code {
+                        KeyCode::Esc => {
+                            state.close_form();
+                        }
+                        KeyCode::Char(' ') | KeyCode::Enter => {
+                            state.new_favorite = !state.new_favorite;
+                        }
+                        KeyCode::Tab => {
+                            state.next_field();
+                        }
+                        KeyCode::BackTab => {
+                            state.previous_field();
+                        }
+                        _ => {}
+                    }
+                }
+
+                InputMode::Submit => {
+                    match key.code {
+                        KeyCode::Esc => {
+                            state.close_form();
+                        }
+                        KeyCode::BackTab => {
+                            state.previous_field();
+                        }
                         KeyCode::Enter => {
                             if state.edit_mode {
                                 state.edit();
@@ -426,6 +1552,27 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, state: &mut PassMng) -> Resul
                         KeyCode::Char('d') => {
                             state.check_delete();
                         }
+                        KeyCode::Char('t') => {
+                            state.copy_totp();
+                        }
+                        KeyCode::Char('r') => {
+                            state.toggle_reveal();
+                        }
+                        KeyCode::Char('R') => {
+                            state.show_secrets = !state.show_secrets;
+                        }
+                        KeyCode::Char('h') => {
+                            state.open_history();
+                        }
+                        KeyCode::Char('z') => {
+                            state.undo_delete();
+                        }
+                        KeyCode::Char('m') => {
+                            state.open_move_entry();
+                        }
+                        KeyCode::Char(c @ '1'..='9') => {
+                            state.copy_custom_field(c as usize - '1' as usize);
+                        }
                         _ => {}
                     }
                 }
@@ -441,13 +1588,265 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, state: &mut PassMng) -> Resul
                         _ => {}
                     }
                 }
-            }
 
-        }
-    }
-}
+                InputMode::Vaults => {
+                    match key.code {
+                        KeyCode::Esc => {
+                            state.change_mode(InputMode::Normal);
+                        }
+                        KeyCode::Up => {
+                            state.move_vault_selection(true);
+                        }
+                        KeyCode::Down => {
+                            state.move_vault_selection(false);
+                        }
+                        KeyCode::Enter => {
+                            state.vault_error = None;
+                            state.change_mode(InputMode::VaultKey);
+                        }
+                        _ => {}
+                    }
+                }
+
+                InputMode::VaultKey => {
+                    match key.code {
+                        KeyCode::Esc => {
+                            state.vault_key.clear();
+                            state.change_mode(InputMode::Vaults);
+                        }
+                        KeyCode::Char(c) => {
+                            state.vault_key.push(c);
+                        }
+                        KeyCode::Backspace => {
+                            state.vault_key.pop();
+                        }
+                        KeyCode::Enter => {
+                            state.switch_vault();
+                        }
+                        _ => {}
+                    }
+                }
+
+                InputMode::Audit => {
+                    match key.code {
+                        KeyCode::Esc => {
+                            state.change_mode(InputMode::Normal);
+                        }
+                        KeyCode::Up => {
+                            state.move_audit_selection(true);
+                        }
+                        KeyCode::Down => {
+                            state.move_audit_selection(false);
+                        }
+                        KeyCode::Enter => {
+                            state.jump_to_finding();
+                        }
+                        _ => {}
+                    }
+                }
+
+                InputMode::History => {
+                    match key.code {
+                        KeyCode::Esc => {
+                            state.close_history();
+                        }
+                        KeyCode::Up => {
+                            state.move_history_selection(true);
+                        }
+                        KeyCode::Down => {
+                            state.move_history_selection(false);
+                        }
+                        KeyCode::Char('p') => {
+                            state.copy_old_password();
+                        }
+                        KeyCode::Char('r') => {
+                            state.toggle_reveal();
+                        }
+                        KeyCode::Enter => {
+                            state.restore_old_password();
+                        }
+                        _ => {}
+                    }
+                }
+
+                InputMode::Trash => {
+                    match key.code {
+                        KeyCode::Esc => {
+                            state.close_trash();
+                        }
+                        KeyCode::Up => {
+                            state.move_trash_selection(true);
+                        }
+                        KeyCode::Down => {
+                            state.move_trash_selection(false);
+                        }
+                        KeyCode::Enter => {
+                            state.restore_from_trash();
+                        }
+                        KeyCode::Char('d') => {
+                            state.check_purge();
+                        }
+                        _ => {}
+                    }
+                }
+
+                InputMode::Purge => {
+                    match key.code {
+                        KeyCode::Char('n') => {
+                            state.change_mode(InputMode::Trash);
+                        }
+                        KeyCode::Char('y') => {
+                            state.purge();
+                        }
+                        _ => {}
+                    }
+                }
+
+                InputMode::Folders => {
+                    match key.code {
+                        KeyCode::Esc => {
+                            state.change_mode(InputMode::Normal);
+                        }
+                        KeyCode::Up => {
+                            state.move_folder_selection(true);
+                        }
+                        KeyCode::Down => {
+                            state.move_folder_selection(false);
+                        }
+                        KeyCode::Left => {
+                            state.fold_folder(true);
+                        }
+                        KeyCode::Right => {
+                            state.fold_folder(false);
+                        }
+                        KeyCode::Enter => {
+                            state.change_mode(InputMode::List);
+                        }
+                        KeyCode::Char('a') => {
+                            state.start_folder_name(false);
+                        }
+                        KeyCode::Char('e') => {
+                            state.start_folder_name(true);
+                        }
+                        KeyCode::Char('d') => {
+                            state.check_delete_folder();
+                        }
+                        _ => {}
+                    }
+                }
+
+                InputMode::FolderName => {
+                    match key.code {
+                        KeyCode::Esc => {
+                            state.folder_name.clear();
+                            state.change_mode(InputMode::Folders);
+                        }
+                        KeyCode::Char(c) => {
+                            state.folder_name.push(c);
+                        }
+                        KeyCode::Backspace => {
+                            state.folder_name.pop();
+                        }
+                        KeyCode::Enter => {
+                            state.save_folder_name();
+                        }
+                        _ => {}
+                    }
+                }
+
+                InputMode::DeleteFolder => {
+                    match key.code {
+                        KeyCode::Char('n') => {
+                            state.change_mode(InputMode::Folders);
+                        }
+                        KeyCode::Char('y') => {
+                            state.delete_folder();
+                        }
+                        _ => {}
+                    }
+                }
+
+                InputMode::MoveEntry => {
+                    match key.code {
+                        KeyCode::Esc => {
+                            state.change_mode(InputMode::List);
+                        }
+                        KeyCode::Up => {
+                            state.move_move_selection(true);
+                        }
+                        KeyCode::Down => {
+                            state.move_move_selection(false);
+                        }
+                        KeyCode::Enter => {
+                            state.move_entry();
+                        }
+                        _ => {}
+                    }
+                }
+
+                InputMode::Locked => {
+                    match key.code {
+                        KeyCode::Esc => {
+                            return Ok(());
+                        }
+                        KeyCode::Char(c) => {
+                            state.unlock_key.push(c);
+                        }
+                        KeyCode::Backspace => {
+                            state.unlock_key.pop();
+                        }
+                        KeyCode::Enter => {
+                            state.unlock();
+                        }
+                        _ => {}
+                    }
+                }
+
+                InputMode::ChangeKey => {
+                    match key.code {
+                        KeyCode::Esc => {
+                            state.close_change_key();
+                        }
+                        KeyCode::Char(c) => {
+                            state.rekey_fields[state.rekey_field].push(c);
+                        }
+                        KeyCode::Backspace => {
+                            state.rekey_fields[state.rekey_field].pop();
+                        }
+                        KeyCode::Tab => {
+                            state.rekey_field = (state.rekey_field + 1) % 3;
+                        }
+                        KeyCode::BackTab => {
+                            state.rekey_field = (state.rekey_field + 2) % 3;
+                        }
+                        KeyCode::Enter => {
+                            state.change_key();
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
+        }
+    }
+}
+
+fn ui<B: Backend>(f: &mut Frame<B>, state: &mut PassMng) {
+    if let InputMode::Locked = state.mode {
+        lock_screen(f, state);
+        return;
+    }
+
+    let screen_chunk = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Min(1),
+                Constraint::Length(1)
+            ].as_ref()
+        )
+        .split(f.size());
 
-fn ui<B: Backend>(f: &mut Frame<B>, state: &mut PassMng) {
     let parent_chunk = Layout::default()
         .direction(Direction::Horizontal)
         .constraints(
@@ -456,23 +1855,51 @@ fn ui<B: Backend>(f: &mut Frame<B>, state: &mut PassMng) {
                 Constraint::Percentage(50)
             ].as_ref()
         )
-        .split(f.size());
+        .split(screen_chunk[0]);
 
     let new_section_block = Block::default()
-        .title("New Password")
+        .title(match state.folder {
+            Some(id) if !state.edit_mode => format!("New Password (in {})", folders::path(&state.folders, id)),
+            _ => "New Password".to_owned()
+        })
         .borders(Borders::ALL)
         .border_type(BorderType::Rounded);
     f.render_widget(new_section_block, parent_chunk[0]);
     new_section(f, state, parent_chunk[0]);
 
+    let list_chunk = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [
+                Constraint::Percentage(30),
+                Constraint::Percentage(70)
+            ].as_ref()
+        )
+        .split(parent_chunk[1]);
+    folder_section(f, state, list_chunk[0]);
+
     let list_section_block = Block::default()
-        .title("List of passwords")
+        .title(match state.folder {
+            Some(id) => format!("List of passwords ({}: {})", state.vault_name(), folders::path(&state.folders, id)),
+            None => format!("List of passwords ({})", state.vault_name())
+        })
         .borders(Borders::ALL)
         .border_type(BorderType::Rounded);
-    f.render_widget(list_section_block, parent_chunk[1]);
-    list_section(f, state, parent_chunk[1]);
+    f.render_widget(list_section_block, list_chunk[1]);
+    list_section(f, state, list_chunk[1]);
+    status_bar(f, state, screen_chunk[1]);
 
+    custom_field_popup(f, state);
+    folder_name_popup(f, state);
+    move_entry_popup(f, state);
+    generator_popup(f, state);
+    vault_popup(f, state);
+    change_key_popup(f, state);
+    audit_popup(f, state);
+    history_popup(f, state);
+    trash_popup(f, state);
     delete_popup(f, state);
+    error_popup(f, state);
 }
 
 fn new_section<B: Backend>(f: &mut Frame<B>, state: &mut PassMng, area: Rect) {
@@ -486,6 +1913,10 @@ fn new_section<B: Backend>(f: &mut Frame<B>, state: &mut PassMng, area: Rect) {
                 Constraint::Length(3),
                 Constraint::Length(3),
                 Constraint::Length(3),
+                Constraint::Length(3),
+                Constraint::Length(5),
+                Constraint::Length(5),
+                Constraint::Length(3),
             ].as_ref()
         )
         .split(area);
@@ -509,14 +1940,91 @@ fn new_section<B: Backend>(f: &mut Frame<B>, state: &mut PassMng, area: Rect) {
         });
     f.render_widget(username_input, new_section_chunk[2]);
 
-    let password_input = Paragraph::new(state.new_password.to_owned())
-        .block(Block::default().title("Password").borders(Borders::ALL).border_type(BorderType::Rounded))
+    let password_input = Paragraph::new(masked(&state.new_password, state.peek))
+        .block(Block::default().title("Password (Ctrl+R: peek)").borders(Borders::ALL).border_type(BorderType::Rounded))
         .style(match state.mode {
             InputMode::Password => Style::default().fg(Color::Yellow),
             _ => Style::default()
         });
     f.render_widget(password_input, new_section_chunk[3]);
 
+    let url_input = Paragraph::new(state.new_urls.to_owned())
+        .block(Block::default().title("URLs (space separated)").borders(Borders::ALL).border_type(BorderType::Rounded))
+        .style(match state.mode {
+            InputMode::Url => Style::default().fg(Color::Yellow),
+            _ => Style::default()
+        });
+    f.render_widget(url_input, new_section_chunk[4]);
+
+    let tags_input = Paragraph::new(state.new_tags.to_owned())
+        .block(Block::default().title("Tags (comma separated)").borders(Borders::ALL).border_type(BorderType::Rounded))
+        .style(match state.mode {
+            InputMode::Tags => Style::default().fg(Color::Yellow),
+            _ => Style::default()
+        });
+    let tags_chunk = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [
+                Constraint::Percentage(50),
+                Constraint::Percentage(50)
+            ].as_ref()
+        )
+        .split(new_section_chunk[5]);
+    f.render_widget(tags_input, tags_chunk[0]);
+
+    let totp_title = match &state.form_error {
+        Some(e) => e.to_owned(),
+        None => "TOTP secret or otpauth:// URI (Ctrl+R: peek)".to_owned()
+    };
+    let totp_input = Paragraph::new(masked(&state.new_totp, state.peek))
+        .block(Block::default().title(totp_title).borders(Borders::ALL).border_type(BorderType::Rounded))
+        .style(match (state.mode, &state.form_error) {
+            (_, Some(_)) => Style::default().fg(Color::Red),
+            (InputMode::Totp, None) => Style::default().fg(Color::Yellow),
+            _ => Style::default()
+        });
+    f.render_widget(totp_input, tags_chunk[1]);
+
+    let notes_input = Paragraph::new(state.new_notes.to_owned())
+        .block(Block::default().title("Notes").borders(Borders::ALL).border_type(BorderType::Rounded))
+        .style(match state.mode {
+            InputMode::Notes => Style::default().fg(Color::Yellow),
+            _ => Style::default()
+        });
+    f.render_widget(notes_input, new_section_chunk[6]);
+
+    let custom_fields: Vec<ListItem> = state.new_custom_fields.iter()
+        .map(|field| ListItem::new(format!("{}: {}", field.name, field.display_value(state.show_secrets))))
+        .collect();
+    let custom_fields_list = List::new(custom_fields)
+        .block(Block::default().title("Custom fields (A: add, E: edit, D: delete, H: hide)").borders(Borders::ALL).border_type(BorderType::Rounded))
+        .highlight_symbol("->")
+        .style(match state.mode {
+            InputMode::CustomFields | InputMode::CustomField => Style::default().fg(Color::Yellow),
+            _ => Style::default()
+        });
+    f.render_stateful_widget(custom_fields_list, new_section_chunk[7], &mut state.custom_fields_state);
+
+    let buttons_chunk = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(
+            [
+                Constraint::Percentage(50),
+                Constraint::Percentage(50)
+            ].as_ref()
+        )
+        .split(new_section_chunk[8]);
+
+    let favorite_checkbox = Paragraph::new(if state.new_favorite { "[x] Favorite" } else { "[ ] Favorite" })
+        .alignment(Alignment::Center)
+        .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded))
+        .style(match state.mode {
+            InputMode::Favorite => Style::default().fg(Color::Yellow),
+            _ => Style::default()
+        });
+    f.render_widget(favorite_checkbox, buttons_chunk[0]);
+
     let submit_btn = Paragraph::new("Submit")
         .alignment(Alignment::Center)
         .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded))
@@ -524,39 +2032,103 @@ fn new_section<B: Backend>(f: &mut Frame<B>, state: &mut PassMng, area: Rect) {
             InputMode::Submit => Style::default().fg(Color::Yellow),
             _ => Style::default()
         });
-    f.render_widget(submit_btn, new_section_chunk[4]);
+    f.render_widget(submit_btn, buttons_chunk[1]);
+}
+
+// the folder tree, with the number of entries in each folder and the folders below it
+fn folder_section<B: Backend>(f: &mut Frame<B>, state: &mut PassMng, area: Rect) {
+    let counts = folders::counts(&state.folders, &state.passwords);
+    let rows = state.folder_rows();
+    let mut items = vec![ListItem::new(format!("All ({})", state.passwords.len()))];
+    items.extend(rows.iter().map(|row| {
+        let folder = state.folders.iter().find(|folder| folder.id == row.id).unwrap();
+        let marker = if folders::children(&state.folders, Some(row.id)).is_empty() {
+            "  "
+        }else if state.collapsed.contains(&row.id) {
+            "▸ "
+        }else {
+            "▾ "
+        };
+        let count = counts.get(&row.id).copied().unwrap_or(0);
+        ListItem::new(format!("{}{}{} ({})", "  ".repeat(row.depth), marker, folder.name, count))
+    }));
+    let selected = state.folder.and_then(|id| rows.iter().position(|row| row.id == id));
+    state.folder_state.select(Some(selected.map(|i| i + 1).unwrap_or(0)));
+
+    let list = List::new(items)
+        .block(Block::default().title("Folders").borders(Borders::ALL).border_type(BorderType::Rounded))
+        .highlight_symbol("->")
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD))
+        .style(match state.mode {
+            InputMode::Folders | InputMode::FolderName | InputMode::DeleteFolder => Style::default().fg(Color::Yellow),
+            _ => Style::default()
+        });
+    f.render_stateful_widget(list, area, &mut state.folder_state);
 }
 
 fn list_section<B: Backend>(f: &mut Frame<B>, state: &mut PassMng, area: Rect) {
-    let list_to_show = if state.search_list.is_empty() {
-        state.passwords.to_owned()
-    }else {
-        state.search_list.to_owned()
-    };
-    let items: Vec<ListItem> = list_to_show.into_iter()
+    let list_to_show = state.visible();
+    let query = Query::parse(&state.search_txt);
+    let items: Vec<ListItem> = list_to_show.iter()
         .map(|item| {
-            match state.mode {
-                InputMode::List => {
-                    ListItem::new(format!("{}: {} - {}", item.title.to_owned(), item.username.to_owned(), item.password.to_owned()))
-                }
-                _ => {
-                    ListItem::new(Span::from(item.title))
+            let favorite = if item.favorite { "* " } else { "" };
+            let mut line = vec![Span::raw(favorite)];
+            if state.breaches.contains_key(&item.id) {
+                line.push(Span::styled("! ", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)));
+            }
+            line.extend(highlighted(&item.title, &query.highlight_title(&item.title)));
+            if state.mode == InputMode::List {
+                let totp = match &item.totp {
+                    Some(totp) => {
+                        let (code, remaining) = totp.now();
+                        format!(" {} ({}s)", code, remaining)
+                    }
+                    None => String::new()
+                };
+                let password = if state.is_revealed(item) { item.password.expose() } else { "********" };
+                line.push(Span::raw(": "));
+                line.extend(highlighted(&item.username, &query.highlight_username(&item.username)));
+                line.push(Span::raw(format!(" - {}{}", password, totp)));
+            }
+            if !item.tags.is_empty() {
+                line.push(Span::raw(" ["));
+                for (i, tag) in item.tags.iter().enumerate() {
+                    if i > 0 {
+                        line.push(Span::raw(", "));
+                    }
+                    line.extend(highlighted(tag, &query.highlight_tag(tag)));
                 }
+                line.push(Span::raw("]"));
             }
+            ListItem::new(Spans::from(line))
         })
         .collect();
 
+    let selected = match (state.mode, state.list_state.selected()) {
+        (InputMode::List, Some(index)) => list_to_show.get(index),
+        _ => None
+    };
+
     let list_chunks = Layout::default()
         .margin(2)
         .direction(Direction::Vertical)
         .constraints(
             [
                 Constraint::Length(3),
-                Constraint::Min(1)
+                Constraint::Min(1),
+                Constraint::Length(match selected {
+                    Some(item) => 14 + item.custom_fields.len() as u16,
+                    None => 0
+                })
             ].as_ref()
         )
         .split(area);
 
+    if let Some(item) = selected {
+        let folder = item.folder.map(|id| folders::path(&state.folders, id)).unwrap_or_else(|| "-".to_owned());
+        details_section(f, item, state.is_revealed(item), &folder, list_chunks[2]);
+    }
+
     let search_input = Paragraph::new(state.search_txt.to_owned())
         .block(Block::default().title("Search").borders(Borders::ALL).border_type(BorderType::Rounded))
         .style(match state.mode {
@@ -572,16 +2144,120 @@ fn list_section<B: Backend>(f: &mut Frame<B>, state: &mut PassMng, area: Rect) {
     f.render_stateful_widget(list, list_chunks[1], &mut state.list_state);
 }
 
-fn delete_popup<B: Backend>(f: &mut Frame<B>, state: &mut PassMng) {
-    if let InputMode::Delete = state.mode {
-        let block = Block::default()
-            .title("DELETE")
-            .title_alignment(Alignment::Center)
-            .borders(Borders::ALL)
-            .border_type(BorderType::Rounded);
-        let area = centered_rect(60, 25, f.size());
-        f.render_widget(Clear, area); //this clears out the background
-        f.render_widget(block, area);
+// the text with the characters the search matched picked out
+fn highlighted(text: &str, positions: &[usize]) -> Vec<Span<'static>> {
+    let style = |matched: bool| if matched {
+        Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)
+    }else {
+        Style::default()
+    };
+    let mut spans = vec![];
+    let mut run = String::new();
+    let mut run_matched = false;
+    for (i, c) in text.chars().enumerate() {
+        let matched = positions.contains(&i);
+        if matched != run_matched && !run.is_empty() {
+            spans.push(Span::styled(std::mem::take(&mut run), style(run_matched)));
+        }
+        run_matched = matched;
+        run.push(c);
+    }
+    spans.push(Span::styled(run, style(run_matched)));
+    spans
+}
+
+fn lock_screen<B: Backend>(f: &mut Frame<B>, state: &mut PassMng) {
+    let block = Block::default()
+        .title(format!("LOCKED ({})", state.vault_name()))
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded);
+    let area = centered_rect(50, 30, f.size());
+    f.render_widget(block, area);
+
+    let chunk = Layout::default()
+        .margin(2)
+        .constraints(
+            [
+                Constraint::Length(3),
+                Constraint::Length(2),
+                Constraint::Length(1),
+            ].as_ref()
+        )
+        .split(area);
+
+    let key_input = Paragraph::new("*".repeat(state.unlock_key.expose().chars().count()))
+        .block(Block::default().title("Passphrase").borders(Borders::ALL).border_type(BorderType::Rounded))
+        .style(Style::default().fg(Color::Yellow));
+    f.render_widget(key_input, chunk[0]);
+
+    if let Some(error) = &state.unlock_error {
+        let text = Paragraph::new(error.to_owned())
+            .style(Style::default().fg(Color::Red))
+            .alignment(Alignment::Center);
+        f.render_widget(text, chunk[1]);
+    }
+
+    let keys_desc = Paragraph::new("Enter: unlock, Esc: quit")
+        .alignment(Alignment::Center);
+    f.render_widget(keys_desc, chunk[2]);
+}
+
+fn status_bar<B: Backend>(f: &mut Frame<B>, state: &mut PassMng, area: Rect) {
+    let mut status = match state.clipboard.remaining() {
+        Some(remaining) => format!(" Clipboard will be cleared in {}s", remaining.as_secs() + 1),
+        None => String::new()
+    };
+    if state.show_secrets {
+        status.push_str(" Secrets are shown (Shift+R to hide)");
+    }
+    let status = Paragraph::new(status)
+        .style(Style::default().fg(Color::Yellow));
+    f.render_widget(status, area);
+}
+
+fn details_section<B: Backend>(f: &mut Frame<B>, item: &Password, reveal: bool, folder: &str, area: Rect) {
+    let last_used = match item.last_used_at {
+        Some(timestamp) => format_time(timestamp),
+        None => "never".to_owned()
+    };
+    let mut lines = vec![
+        Spans::from(format!("URLs:      {}", item.urls.join(" "))),
+        Spans::from(format!("Tags:      {}", item.tags.join(", "))),
+        Spans::from(format!("Folder:    {}", folder)),
+        Spans::from(format!("Favorite:  {}", if item.favorite { "yes" } else { "no" })),
+        Spans::from(format!("TOTP:      {}", match &item.totp {
+            Some(totp) => {
+                let (code, remaining) = totp.now();
+                format!("{} ({}s left)", code, remaining)
+            }
+            None => "-".to_owned()
+        })),
+        Spans::from(format!("Created:   {}", format_time(item.created_at))),
+        Spans::from(format!("Modified:  {}", format_time(item.modified_at))),
+        Spans::from(format!("Last used: {}", last_used)),
+    ];
+    for (i, field) in item.custom_fields.iter().enumerate() {
+        lines.push(Spans::from(format!("{}. {}: {}", i + 1, field.name, field.display_value(reveal))));
+    }
+    lines.push(Spans::from("Notes:"));
+    lines.extend(item.notes.lines().map(|line| Spans::from(format!("  {}", line))));
+
+    let details = Paragraph::new(lines)
+        .block(Block::default().title(item.title.to_owned()).borders(Borders::ALL).border_type(BorderType::Rounded));
+    f.render_widget(details, area);
+}
+
+fn delete_popup<B: Backend>(f: &mut Frame<B>, state: &mut PassMng) {
+    if let InputMode::Delete | InputMode::Purge | InputMode::DeleteFolder = state.mode {
+        let block = Block::default()
+            .title("DELETE")
+            .title_alignment(Alignment::Center)
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded);
+        let area = centered_rect(60, 25, f.size());
+        f.render_widget(Clear, area); //this clears out the background
+        f.render_widget(block, area);
 
         let chunk = Layout::default()
             .margin(2)
@@ -593,7 +2269,11 @@ fn delete_popup<B: Backend>(f: &mut Frame<B>, state: &mut PassMng) {
             )
             .split(area);
 
-        let text = Paragraph::new("Are you sure?")
+        let text = Paragraph::new(match state.mode {
+            InputMode::Purge => "Delete it for good? This can't be undone",
+            InputMode::DeleteFolder => "Delete the folder? What's in it moves up a level",
+            _ => "Are you sure?"
+        })
             .style(Style::default().fg(Color::Red))
             .alignment(Alignment::Center);
         f.render_widget(text, chunk[0]);
@@ -604,6 +2284,461 @@ fn delete_popup<B: Backend>(f: &mut Frame<B>, state: &mut PassMng) {
     }
 }
 
+fn custom_field_popup<B: Backend>(f: &mut Frame<B>, state: &mut PassMng) {
+    if let InputMode::CustomField = state.mode {
+        let block = Block::default()
+            .title("CUSTOM FIELD")
+            .title_alignment(Alignment::Center)
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded);
+        let area = centered_rect(60, 30, f.size());
+        f.render_widget(Clear, area);
+        f.render_widget(block, area);
+
+        let chunk = Layout::default()
+            .margin(2)
+            .constraints(
+                [
+                    Constraint::Length(3),
+                    Constraint::Length(3),
+                ].as_ref()
+            )
+            .split(area);
+
+        // the value of a hidden field stays masked while it's edited, like the password
+        let hidden = state.custom_field_index.map(|index| state.new_custom_fields[index].hidden).unwrap_or(false);
+        for (i, title) in ["Name", if hidden { "Value (Ctrl+R: peek)" } else { "Value" }].iter().enumerate() {
+            let text = if i == 1 && hidden {
+                masked(&state.custom_field_inputs[i], state.peek)
+            }else {
+                state.custom_field_inputs[i].expose().to_owned()
+            };
+            let input = Paragraph::new(text)
+                .block(Block::default().title(*title).borders(Borders::ALL).border_type(BorderType::Rounded))
+                .style(if state.custom_field_input == i {
+                    Style::default().fg(Color::Yellow)
+                }else {
+                    Style::default()
+                });
+            f.render_widget(input, chunk[i]);
+        }
+    }
+}
+
+fn folder_name_popup<B: Backend>(f: &mut Frame<B>, state: &mut PassMng) {
+    if let InputMode::FolderName = state.mode {
+        let block = Block::default()
+            .title(if state.renamed_folder.is_some() { "RENAME FOLDER" } else { "NEW FOLDER" })
+            .title_alignment(Alignment::Center)
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded);
+        let area = centered_rect(60, 25, f.size());
+        f.render_widget(Clear, area);
+        f.render_widget(block, area);
+
+        let chunk = Layout::default()
+            .margin(2)
+            .constraints(
+                [
+                    Constraint::Length(3),
+                    Constraint::Length(1),
+                ].as_ref()
+            )
+            .split(area);
+
+        let input = Paragraph::new(state.folder_name.to_owned())
+            .block(Block::default().title("Name").borders(Borders::ALL).border_type(BorderType::Rounded))
+            .style(Style::default().fg(Color::Yellow));
+        f.render_widget(input, chunk[0]);
+
+        let (text, color) = match &state.folder_error {
+            Some(error) => (error.to_owned(), Color::Red),
+            None => ("Enter to save, Esc to cancel".to_owned(), Color::Reset)
+        };
+        let text = Paragraph::new(text)
+            .style(Style::default().fg(color))
+            .alignment(Alignment::Center);
+        f.render_widget(text, chunk[1]);
+    }
+}
+
+fn move_entry_popup<B: Backend>(f: &mut Frame<B>, state: &mut PassMng) {
+    if let InputMode::MoveEntry = state.mode {
+        let block = Block::default()
+            .title("MOVE TO FOLDER")
+            .title_alignment(Alignment::Center)
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded);
+        let area = centered_rect(50, 60, f.size());
+        f.render_widget(Clear, area);
+        f.render_widget(block, area);
+
+        let chunk = Layout::default()
+            .margin(2)
+            .constraints(
+                [
+                    Constraint::Min(1),
+                    Constraint::Length(1),
+                ].as_ref()
+            )
+            .split(area);
+
+        // every folder, collapsed or not
+        let mut items = vec![ListItem::new("(top level)")];
+        items.extend(folders::rows(&state.folders, &HashSet::new()).iter().map(|row| {
+            let folder = state.folders.iter().find(|folder| folder.id == row.id).unwrap();
+            ListItem::new(format!("{}{}", "  ".repeat(row.depth), folder.name))
+        }));
+        let list = List::new(items)
+            .highlight_symbol("->")
+            .highlight_style(Style::default().bg(Color::DarkGray));
+        f.render_stateful_widget(list, chunk[0], &mut state.move_state);
+
+        let keys_desc = Paragraph::new("Enter: move here, Esc: cancel")
+            .alignment(Alignment::Center);
+        f.render_widget(keys_desc, chunk[1]);
+    }
+}
+
+fn generator_popup<B: Backend>(f: &mut Frame<B>, state: &mut PassMng) {
+    if let InputMode::Generator = state.mode {
+        let block = Block::default()
+            .title("GENERATE PASSWORD")
+            .title_alignment(Alignment::Center)
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded);
+        let area = centered_rect(70, 50, f.size());
+        f.render_widget(Clear, area);
+        f.render_widget(block, area);
+
+        let chunk = Layout::default()
+            .margin(2)
+            .constraints(
+                [
+                    Constraint::Length(5),
+                    Constraint::Length(3),
+                    Constraint::Length(2),
+                    Constraint::Min(1),
+                ].as_ref()
+            )
+            .split(area);
+
+        let g = &state.generator;
+        let check = |enabled: bool| if enabled { "[x]" } else { "[ ]" };
+        let settings = match g.mode {
+            GeneratorMode::Characters => vec![
+                Spans::from("Mode: characters (W)"),
+                Spans::from(format!("Length: {} (Left/Right)", g.length)),
+                Spans::from(format!(
+                    "{} Lowercase (L)  {} Uppercase (U)  {} Digits (D)  {} Symbols (S)",
+                    check(g.lowercase), check(g.uppercase), check(g.digits), check(g.symbols)
+                )),
+                Spans::from(format!(
+                    "{} Exclude ambiguous (A)  {} Must include every class (M)",
+                    check(g.exclude_ambiguous), check(g.must_include)
+                ))
+            ],
+            GeneratorMode::Passphrase => vec![
+                Spans::from("Mode: passphrase (W)"),
+                Spans::from(format!("Words: {} (Left/Right)", g.words))
+            ]
+        };
+        f.render_widget(Paragraph::new(settings), chunk[0]);
+
+        let (password, entropy) = match &state.generated {
+            Ok(generated) => (
+                Paragraph::new(generated.password.expose()),
+                format!("Entropy: {:.0} bits ({})", generated.bits, Strength::from_bits(generated.bits).label())
+            ),
+            Err(e) => (Paragraph::new(e.to_owned()).style(Style::default().fg(Color::Red)), String::new())
+        };
+        let password = password
+            .block(Block::default().borders(Borders::ALL).border_type(BorderType::Rounded));
+        f.render_widget(password, chunk[1]);
+        f.render_widget(Paragraph::new(entropy), chunk[2]);
+
+        let keys_desc = Paragraph::new("R: regenerate, Enter: use it, Esc: cancel")
+            .alignment(Alignment::Center);
+        f.render_widget(keys_desc, chunk[3]);
+    }
+}
+
+fn vault_popup<B: Backend>(f: &mut Frame<B>, state: &mut PassMng) {
+    if let InputMode::Vaults | InputMode::VaultKey = state.mode {
+        let block = Block::default()
+            .title("SWITCH VAULT")
+            .title_alignment(Alignment::Center)
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded);
+        let area = centered_rect(60, 50, f.size());
+        f.render_widget(Clear, area);
+        f.render_widget(block, area);
+
+        let chunk = Layout::default()
+            .margin(2)
+            .constraints(
+                [
+                    Constraint::Min(1),
+                    Constraint::Length(3),
+                    Constraint::Length(1),
+                ].as_ref()
+            )
+            .split(area);
+
+        let items: Vec<ListItem> = state.vaults.iter()
+            .map(|(name, path)| ListItem::new(format!("{} ({})", name, path.display())))
+            .collect();
+        let list = List::new(items)
+            .highlight_symbol("->")
+            .highlight_style(Style::default().add_modifier(Modifier::BOLD));
+        f.render_stateful_widget(list, chunk[0], &mut state.vault_state);
+
+        let key_input = Paragraph::new("*".repeat(state.vault_key.expose().chars().count()))
+            .block(Block::default().title("Passphrase").borders(Borders::ALL).border_type(BorderType::Rounded))
+            .style(match state.mode {
+                InputMode::VaultKey => Style::default().fg(Color::Yellow),
+                _ => Style::default()
+            });
+        f.render_widget(key_input, chunk[1]);
+
+        if let Some(error) = &state.vault_error {
+            let text = Paragraph::new(error.to_owned())
+                .style(Style::default().fg(Color::Red))
+                .alignment(Alignment::Center);
+            f.render_widget(text, chunk[2]);
+        }
+    }
+}
+
+fn change_key_popup<B: Backend>(f: &mut Frame<B>, state: &mut PassMng) {
+    if let InputMode::ChangeKey = state.mode {
+        let block = Block::default()
+            .title("CHANGE PASSPHRASE")
+            .title_alignment(Alignment::Center)
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded);
+        let area = centered_rect(60, 50, f.size());
+        f.render_widget(Clear, area);
+        f.render_widget(block, area);
+
+        let chunk = Layout::default()
+            .margin(2)
+            .constraints(
+                [
+                    Constraint::Length(3),
+                    Constraint::Length(3),
+                    Constraint::Length(3),
+                    Constraint::Length(2),
+                ].as_ref()
+            )
+            .split(area);
+
+        let titles = ["Current passphrase", "New passphrase", "Confirm new passphrase"];
+        for (i, title) in titles.iter().enumerate() {
+            let input = Paragraph::new("*".repeat(state.rekey_fields[i].expose().chars().count()))
+                .block(Block::default().title(*title).borders(Borders::ALL).border_type(BorderType::Rounded))
+                .style(if state.rekey_field == i {
+                    Style::default().fg(Color::Yellow)
+                }else {
+                    Style::default()
+                });
+            f.render_widget(input, chunk[i]);
+        }
+
+        let (text, color) = match &state.rekey_result {
+            Some(Ok(message)) => (message.to_owned(), Color::Green),
+            Some(Err(error)) => (error.to_owned(), Color::Red),
+            None => ("Tab to move between fields, Enter to change".to_owned(), Color::Reset)
+        };
+        let text = Paragraph::new(text)
+            .style(Style::default().fg(color))
+            .alignment(Alignment::Center);
+        f.render_widget(text, chunk[3]);
+    }
+}
+
+fn audit_popup<B: Backend>(f: &mut Frame<B>, state: &mut PassMng) {
+    if let InputMode::Audit = state.mode {
+        let block = Block::default()
+            .title(format!("PASSWORD AUDIT ({} entries with problems)", state.audit.len()))
+            .title_alignment(Alignment::Center)
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded);
+        let area = centered_rect(80, 70, f.size());
+        f.render_widget(Clear, area);
+        f.render_widget(block, area);
+
+        let chunk = Layout::default()
+            .margin(2)
+            .constraints(
+                [
+                    Constraint::Min(1),
+                    Constraint::Length(1),
+                ].as_ref()
+            )
+            .split(area);
+
+        if state.audit.is_empty() {
+            let text = Paragraph::new("No weak, reused, similar or old passwords")
+                .style(Style::default().fg(Color::Green))
+                .alignment(Alignment::Center);
+            f.render_widget(text, chunk[0]);
+        }else {
+            let items: Vec<ListItem> = state.audit.iter()
+                .map(|finding| {
+                    let mut lines = vec![Spans::from(Span::styled(
+                        format!("{} ({})", finding.title, finding.username),
+                        Style::default().add_modifier(Modifier::BOLD)
+                    ))];
+                    lines.extend(finding.issues.iter().map(|issue| Spans::from(Span::styled(
+                        format!("  {}", issue.describe()),
+                        Style::default().fg(Color::Red)
+                    ))));
+                    ListItem::new(lines)
+                })
+                .collect();
+            let list = List::new(items)
+                .highlight_symbol("->")
+                .highlight_style(Style::default().bg(Color::DarkGray));
+            f.render_stateful_widget(list, chunk[0], &mut state.audit_state);
+        }
+
+        let keys_desc = Paragraph::new("Enter: go to entry, Esc: close")
+            .alignment(Alignment::Center);
+        f.render_widget(keys_desc, chunk[1]);
+    }
+}
+
+fn history_popup<B: Backend>(f: &mut Frame<B>, state: &mut PassMng) {
+    if let InputMode::History = state.mode {
+        let Some(index) = state.selected_index() else {
+            return;
+        };
+        let item = &state.passwords[index];
+        let block = Block::default()
+            .title(format!("PASSWORD HISTORY ({})", item.title))
+            .title_alignment(Alignment::Center)
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded);
+        let area = centered_rect(60, 50, f.size());
+        f.render_widget(Clear, area);
+        f.render_widget(block, area);
+
+        let chunk = Layout::default()
+            .margin(2)
+            .constraints(
+                [
+                    Constraint::Min(1),
+                    Constraint::Length(1),
+                ].as_ref()
+            )
+            .split(area);
+
+        if state.history.is_empty() {
+            let text = Paragraph::new("The password of this entry hasn't been changed")
+                .alignment(Alignment::Center);
+            f.render_widget(text, chunk[0]);
+        }else {
+            let revealed = state.is_revealed(item);
+            let items: Vec<ListItem> = state.history.iter()
+                .map(|old| {
+                    let password = if revealed { old.password.expose() } else { "********" };
+                    ListItem::new(format!("until {}  {}", format_time(old.replaced_at), password))
+                })
+                .collect();
+            let list = List::new(items)
+                .highlight_symbol("->")
+                .highlight_style(Style::default().bg(Color::DarkGray));
+            f.render_stateful_widget(list, chunk[0], &mut state.history_state);
+        }
+
+        let keys_desc = Paragraph::new("P: copy, R: reveal, Enter: restore, Esc: close")
+            .alignment(Alignment::Center);
+        f.render_widget(keys_desc, chunk[1]);
+    }
+}
+
+fn trash_popup<B: Backend>(f: &mut Frame<B>, state: &mut PassMng) {
+    if let InputMode::Trash | InputMode::Purge = state.mode {
+        let block = Block::default()
+            .title(format!("TRASH ({} entries)", state.trash.len()))
+            .title_alignment(Alignment::Center)
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded);
+        let area = centered_rect(80, 70, f.size());
+        f.render_widget(Clear, area);
+        f.render_widget(block, area);
+
+        let chunk = Layout::default()
+            .margin(2)
+            .constraints(
+                [
+                    Constraint::Min(1),
+                    Constraint::Length(1),
+                ].as_ref()
+            )
+            .split(area);
+
+        if state.trash.is_empty() {
+            let text = Paragraph::new("The trash is empty")
+                .alignment(Alignment::Center);
+            f.render_widget(text, chunk[0]);
+        }else {
+            let items: Vec<ListItem> = state.trash.iter()
+                .map(|trashed| ListItem::new(format!(
+                    "{} ({}) deleted {}",
+                    trashed.password.title,
+                    trashed.password.username,
+                    format_time(trashed.deleted_at)
+                )))
+                .collect();
+            let list = List::new(items)
+                .highlight_symbol("->")
+                .highlight_style(Style::default().bg(Color::DarkGray));
+            f.render_stateful_widget(list, chunk[0], &mut state.trash_state);
+        }
+
+        let keys_desc = Paragraph::new(match state.config.trash_days {
+            0 => "Enter: restore, D: delete for good, Esc: close".to_owned(),
+            days => format!("Enter: restore, D: delete for good, Esc: close. Entries are deleted for good after {} days", days)
+        }).alignment(Alignment::Center);
+        f.render_widget(keys_desc, chunk[1]);
+    }
+}
+
+fn error_popup<B: Backend>(f: &mut Frame<B>, state: &mut PassMng) {
+    if let Some(error) = &state.error {
+        let block = Block::default()
+            .title("ERROR")
+            .title_alignment(Alignment::Center)
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded);
+        let area = centered_rect(60, 25, f.size());
+        f.render_widget(Clear, area);
+        f.render_widget(block, area);
+
+        let chunk = Layout::default()
+            .margin(2)
+            .constraints(
+                [
+                    Constraint::Min(2),
+                    Constraint::Length(1),
+                ].as_ref()
+            )
+            .split(area);
+
+        let text = Paragraph::new(error.to_owned())
+            .style(Style::default().fg(Color::Red))
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true });
+        f.render_widget(text, chunk[0]);
+
+        let keys_desc = Paragraph::new("Press any key to continue")
+            .alignment(Alignment::Center);
+        f.render_widget(keys_desc, chunk[1]);
+    }
+}
+
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
         .direction(Direction::Vertical)
@@ -628,4 +2763,159 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
                 .as_ref(),
         )
         .split(popup_layout[1])[1]
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    // a vault with three entries, open in the app with nothing selected
+    fn app(dir: &Path) -> PassMng {
+        let path = dir.join("vault");
+        let db = Database::create(&path, &SecretString::from("key"), Some(4000)).unwrap();
+        for (title, username, password) in [("github", "alice", "one"), ("gitlab", "bob", "two"), ("example", "carol", "three")] {
+            db.insert(&Password::new(title.to_owned(), username.to_owned(), SecretString::from(password))).unwrap();
+        }
+        PassMng::new(db, path, Config::default()).unwrap()
+    }
+
+    fn search(state: &mut PassMng, text: &str) {
+        state.search_txt = text.to_owned();
+        state.search();
+        state.move_down();
+    }
+
+    fn saved(state: &PassMng, title: &str) -> Password {
+        state.db().load().unwrap().into_iter().find(|password| password.title == title).unwrap()
+    }
+
+    #[test]
+    fn step_stops_at_the_ends() {
+        let mut state = ListState::default();
+        step(&mut state, 0, false);
+        assert_eq!(state.selected(), None);
+        step(&mut state, 3, true);
+        assert_eq!(state.selected(), Some(0));
+        step(&mut state, 3, true);
+        assert_eq!(state.selected(), Some(0));
+        for _ in 0..3 {
+            step(&mut state, 3, false);
+        }
+        assert_eq!(state.selected(), Some(2));
+    }
+
+    #[test]
+    fn copy_acts_on_the_search_result() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = app(dir.path());
+        search(&mut state, "example");
+        assert_eq!(state.visible()[0].title, "example");
+        let index = state.selected_index().unwrap();
+        assert_eq!(state.passwords[index].password.expose(), "three");
+
+        state.copy_password();
+        // there's no clipboard to copy to when the tests run headless
+        if state.error.take().is_none() {
+            assert!(saved(&state, "example").last_used_at.is_some());
+            assert!(saved(&state, "github").last_used_at.is_none());
+        }
+    }
+
+    #[test]
+    fn edit_acts_on_the_search_result() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = app(dir.path());
+        search(&mut state, "gitlab");
+        state.start_edit_mode();
+        assert_eq!(state.new_title, "gitlab");
+        state.new_username = "dave".to_owned();
+        state.edit();
+
+        assert!(state.error.is_none());
+        assert_eq!(saved(&state, "gitlab").username, "dave");
+        assert_eq!(saved(&state, "github").username, "alice");
+        assert_eq!(saved(&state, "example").username, "carol");
+        assert!(state.passwords.iter().any(|password| password.username == "dave"));
+    }
+
+    #[test]
+    fn delete_acts_on_the_search_result() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = app(dir.path());
+        search(&mut state, "carol");
+        state.delete();
+
+        let titles: Vec<String> = state.db().load().unwrap().into_iter().map(|password| password.title).collect();
+        assert_eq!(titles, ["github", "gitlab"]);
+        assert_eq!(state.db().load_trash().unwrap()[0].password.title, "example");
+
+        state.undo_delete();
+        assert_eq!(state.db().load().unwrap().len(), 3);
+        assert_eq!(state.visible()[0].title, "example");
+    }
+
+    #[test]
+    fn leaving_the_form_forgets_the_edited_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = app(dir.path());
+        search(&mut state, "github");
+        state.start_edit_mode();
+        state.close_form();
+        assert!(!state.edit_mode);
+
+        // deleting an entry and adding a new one must not touch the one that was edited
+        search(&mut state, "gitlab");
+        state.delete();
+        state.new_title = "new".to_owned();
+        state.new_username = "erin".to_owned();
+        state.insert();
+        assert!(state.error.is_none());
+        assert_eq!(saved(&state, "github").username, "alice");
+        assert_eq!(saved(&state, "new").username, "erin");
+    }
+
+    #[test]
+    fn jumping_to_a_finding_leaves_the_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = app(dir.path());
+        let folder = state.db().insert_folder(None, "Work").unwrap();
+        let gitlab = state.passwords.iter().find(|password| password.title == "gitlab").unwrap().id;
+        state.db().move_to_folder(gitlab, Some(folder)).unwrap();
+        (state.passwords, state.folders) = (state.db().load().unwrap(), state.db().load_folders().unwrap());
+        state.folder = Some(folder);
+
+        state.open_audit();
+        let finding = state.audit.iter().position(|finding| finding.title == "example").unwrap();
+        state.audit_state.select(Some(finding));
+        state.jump_to_finding();
+        assert_eq!(state.folder, None);
+        assert_eq!(state.passwords[state.selected_index().unwrap()].title, "example");
+    }
+
+    #[test]
+    fn editing_a_deleted_entry_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = app(dir.path());
+        search(&mut state, "github");
+        state.start_edit_mode();
+        let id = state.edit_id.unwrap();
+        let index = state.passwords.iter().position(|password| password.id == id).unwrap();
+        state.passwords.remove(index);
+        state.edit();
+        assert!(state.error.is_some());
+        assert!(!state.edit_mode && state.edit_id.is_none());
+    }
+
+    #[test]
+    fn locking_closes_the_popups() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = app(dir.path());
+        state.error = Some("something went wrong".to_owned());
+        state.folder_error = Some("folder name can't be empty".to_owned());
+        state.lock();
+        assert!(state.mode == InputMode::Locked);
+        assert!(state.error.is_none() && state.folder_error.is_none());
+    }
+
+}
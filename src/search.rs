@@ -0,0 +1,192 @@
+use crate::Password;
+
+// which part of an entry a search term looks at, set with a `field:` prefix
+enum Field {
+    Any,
+    Title,
+    Username,
+    Url,
+    Tag,
+    Notes
+}
+
+/// A parsed search like `hub tag:work user:alice`. Every term has to match somewhere, a term
+/// without a prefix can match any field.
+pub struct Query {
+    terms: Vec<(Field, String)>
+}
+
+impl Query {
+
+    pub fn parse(input: &str) -> Query {
+        let terms = input.split_whitespace()
+            .map(|term| {
+                let field = term.split_once(':').and_then(|(prefix, rest)| {
+                    let field = match prefix {
+                        "title" => Field::Title,
+                        "user" | "username" => Field::Username,
+                        "url" => Field::Url,
+                        "tag" => Field::Tag,
+                        "notes" => Field::Notes,
+                        _ => return None
+                    };
+                    Some((field, rest))
+                });
+                // anything else, like a url with its scheme, is searched for as it is
+                let (field, term) = field.unwrap_or((Field::Any, term));
+                (field, term.to_owned())
+            })
+            .filter(|(_, term)| !term.is_empty())
+            .collect();
+        Query { terms }
+    }
+
+    /// How well the entry matches, `None` if some term doesn't match at all.
+    pub fn score(&self, password: &Password) -> Option<i64> {
+        let mut total = 0;
+        for (field, term) in &self.terms {
+            // a hit in the title says more about what's being looked for than one in the notes
+            let candidates: Vec<(&str, i64)> = match field {
+                Field::Any => {
+                    let mut candidates = vec![(password.title.as_str(), 3), (password.username.as_str(), 2), (password.notes.as_str(), 1)];
+                    candidates.extend(password.urls.iter().map(|url| (url.as_str(), 2)));
+                    candidates.extend(password.tags.iter().map(|tag| (tag.as_str(), 2)));
+                    candidates
+                }
+                Field::Title => vec![(password.title.as_str(), 1)],
+                Field::Username => vec![(password.username.as_str(), 1)],
+                Field::Url => password.urls.iter().map(|url| (url.as_str(), 1)).collect(),
+                Field::Tag => password.tags.iter().map(|tag| (tag.as_str(), 1)).collect(),
+                Field::Notes => vec![(password.notes.as_str(), 1)]
+            };
+            total += candidates.into_iter()
+                .filter_map(|(text, weight)| fuzzy_match(term, text).map(|(score, _)| score * weight))
+                .max()?;
+        }
+        Some(total)
+    }
+
+    /// Positions of the characters in `title` that the query matched, for highlighting.
+    pub fn highlight_title(&self, title: &str) -> Vec<usize> {
+        self.highlight(title, |field| matches!(field, Field::Title))
+    }
+
+    /// Same as `highlight_title`, for the username.
+    pub fn highlight_username(&self, username: &str) -> Vec<usize> {
+        self.highlight(username, |field| matches!(field, Field::Username))
+    }
+
+    /// Same as `highlight_title`, for one of the tags.
+    pub fn highlight_tag(&self, tag: &str) -> Vec<usize> {
+        self.highlight(tag, |field| matches!(field, Field::Tag))
+    }
+
+    // terms without a prefix are looked for in every field
+    fn highlight(&self, text: &str, looks_at: fn(&Field) -> bool) -> Vec<usize> {
+        let mut positions: Vec<usize> = self.terms.iter()
+            .filter(|(field, _)| matches!(field, Field::Any) || looks_at(field))
+            .filter_map(|(_, term)| fuzzy_match(term, text))
+            .flat_map(|(_, positions)| positions)
+            .collect();
+        positions.sort_unstable();
+        positions.dedup();
+        positions
+    }
+
+}
+
+/// The entries matching the query, best match first. Equally good matches keep their order.
+pub fn rank<'a>(query: &Query, passwords: &'a [Password]) -> Vec<&'a Password> {
+    let mut matches: Vec<(i64, &Password)> = passwords.iter()
+        .filter_map(|password| query.score(password).map(|score| (score, password)))
+        .collect();
+    matches.sort_by_key(|(score, _)| -score);
+    matches.into_iter().map(|(_, password)| password).collect()
+}
+
+/// Finds the characters of `pattern` in `text` in order, ignoring case, and returns a score
+/// along with the char positions that matched. Consecutive characters and characters at the
+/// start of a word count for more, so "gh" ranks "GitHub" above "lighthouse".
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+    let pattern: Vec<char> = pattern.chars().map(lowercase).collect();
+    let text: Vec<char> = text.chars().collect();
+    let lower: Vec<char> = text.iter().copied().map(lowercase).collect();
+    if pattern.is_empty() {
+        return Some((0, vec![]));
+    }
+
+    // matching greedily can miss a better match further on, so try every place the pattern can start
+    let mut best: Option<(i64, Vec<usize>)> = None;
+    for start in (0..lower.len()).filter(|i| lower[*i] == pattern[0]) {
+        let mut positions = vec![start];
+        let mut next = start + 1;
+        for c in &pattern[1..] {
+            match lower[next..].iter().position(|t| t == c) {
+                Some(offset) => {
+                    positions.push(next + offset);
+                    next += offset + 1;
+                }
+                None => break
+            }
+        }
+        if positions.len() < pattern.len() {
+            // later starts leave even less text to match the rest in
+            break;
+        }
+        let score = score_positions(&text, &positions);
+        if best.as_ref().map(|(best_score, _)| score > *best_score).unwrap_or(true) {
+            best = Some((score, positions));
+        }
+    }
+    best
+}
+
+fn score_positions(text: &[char], positions: &[usize]) -> i64 {
+    let mut score = 0;
+    let mut previous: Option<usize> = None;
+    for &position in positions {
+        score += 1;
+        if is_word_start(text, position) {
+            score += 8;
+        }
+        match previous {
+            Some(p) if position == p + 1 => score += 5,
+            Some(p) => score -= (position - p - 1).min(5) as i64,
+            None if position == 0 => score += 10,
+            None => {}
+        }
+        previous = Some(position);
+    }
+    score
+}
+
+fn is_word_start(text: &[char], position: usize) -> bool {
+    if position == 0 {
+        return true;
+    }
+    let (before, current) = (text[position - 1], text[position]);
+    !before.is_alphanumeric() || (before.is_lowercase() && current.is_uppercase())
+}
+
+// keeps one char per char, so positions in the lowercased text are positions in the original
+fn lowercase(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn highlights_the_fields_a_term_looks_at() {
+        let query = Query::parse("hub user:al tag:wk");
+        assert_eq!(query.highlight_title("GitHub"), [3, 4, 5]);
+        // a term with a prefix only shows up in its own field
+        assert_eq!(query.highlight_title("Alice"), Vec::<usize>::new());
+        assert_eq!(query.highlight_username("alice"), [0, 1]);
+        assert_eq!(query.highlight_tag("work"), [0, 3]);
+        assert_eq!(query.highlight_tag("hubs"), [0, 1, 2]);
+    }
+
+}
@@ -0,0 +1,140 @@
+use std::fmt;
+use rusqlite::types::{FromSql, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use zeroize::Zeroize;
+
+const MIN_CAPACITY: usize = 64;
+
+/// A string holding a password, passphrase or other secret. Its memory is zeroed when it's
+/// dropped and it never shows up in `Debug` output. Where the OS allows, the buffer is also
+/// locked into RAM so it can't be swapped to disk.
+///
+/// Growing a `String` reallocates and leaves the old buffer behind, so `push` moves the
+/// content over by hand and wipes the old buffer.
+#[derive(Default)]
+pub struct SecretString {
+    inner: String,
+    // page locks don't nest, so unlocking can release a page that another secret shares. It's
+    // a best effort on top of the zeroing, which is what actually matters
+    _lock: Option<region::LockGuard>
+}
+
+impl SecretString {
+
+    pub fn new(inner: String) -> SecretString {
+        let _lock = if inner.capacity() > 0 {
+            region::lock(inner.as_ptr(), inner.capacity()).ok()
+        }else {
+            None
+        };
+        SecretString { inner, _lock }
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.inner
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    pub fn push(&mut self, c: char) {
+        if self.inner.len() + c.len_utf8() > self.inner.capacity() {
+            let mut grown = String::with_capacity((self.inner.capacity() * 2).max(MIN_CAPACITY));
+            grown.push_str(&self.inner);
+            *self = SecretString::new(grown);
+        }
+        self.inner.push(c);
+    }
+
+    pub fn pop(&mut self) -> Option<char> {
+        let c = self.inner.pop()?;
+        // pop only moves the length back, the bytes of the char are still in the buffer
+        let len = self.inner.len();
+        unsafe {
+            let bytes = self.inner.as_mut_vec();
+            bytes.set_len(len + c.len_utf8());
+            bytes[len..].zeroize();
+            bytes.set_len(len);
+        }
+        Some(c)
+    }
+
+    pub fn clear(&mut self) {
+        self.inner.zeroize();
+    }
+
+}
+
+impl Drop for SecretString {
+
+    fn drop(&mut self) {
+        self.inner.zeroize();
+    }
+
+}
+
+impl Clone for SecretString {
+
+    fn clone(&self) -> SecretString {
+        let mut inner = String::with_capacity(self.inner.capacity().max(self.inner.len()));
+        inner.push_str(&self.inner);
+        SecretString::new(inner)
+    }
+
+}
+
+impl PartialEq for SecretString {
+
+    fn eq(&self, other: &SecretString) -> bool {
+        self.inner == other.inner
+    }
+
+}
+
+impl From<String> for SecretString {
+
+    fn from(inner: String) -> SecretString {
+        SecretString::new(inner)
+    }
+
+}
+
+impl From<&str> for SecretString {
+
+    fn from(inner: &str) -> SecretString {
+        SecretString::new(inner.to_owned())
+    }
+
+}
+
+impl fmt::Debug for SecretString {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretString(***)")
+    }
+
+}
+
+impl serde::Serialize for SecretString {
+
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.inner)
+    }
+
+}
+
+impl ToSql for SecretString {
+
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::Borrowed(ValueRef::Text(self.inner.as_bytes())))
+    }
+
+}
+
+impl FromSql for SecretString {
+
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<SecretString> {
+        String::column_result(value).map(SecretString::new)
+    }
+
+}
@@ -0,0 +1,74 @@
+use std::error::Error;
+use std::io::Write;
+use std::path::Path;
+use crate::db::Database;
+use crate::secret::SecretString;
+use crate::strength;
+
+/// Walks through creating a vault that doesn't exist yet and returns it along with its passphrase.
+pub fn create_vault(path: &Path) -> Result<(Database, SecretString), Box<dyn Error>> {
+    // scripts have nothing to confirm with, so the passphrase they hand over is taken as is
+    if let Ok(key) = std::env::var("PASSMNG_PASSPHRASE") {
+        let key = SecretString::new(key);
+        return Ok((create(path, &key, None)?, key));
+    }
+
+    println!("No vault found at {}, let's create one.", path.display());
+    println!("There is no way to recover the vault without its passphrase, so pick it carefully.");
+    let key = choose_passphrase("New Passphrase: ", "Confirm Passphrase: ")?;
+
+    let mut kdf_iter = None;
+    if ask("Customize key derivation? [y/N] ")? {
+        let iterations = prompt("KDF iterations (SQLCipher's default is 256000): ")?;
+        let iterations: u32 = match iterations.parse() {
+            Ok(n) if n > 0 => n,
+            _ => return Err("KDF iterations must be a positive number".into())
+        };
+        kdf_iter = Some(iterations);
+    }
+
+    let db = create(path, &key, kdf_iter)?;
+    println!("Vault created.");
+    Ok((db, key))
+}
+
+/// Asks for a new passphrase until it's not empty, is confirmed and, when it's easy to
+/// guess, the user agrees to use it anyway.
+pub fn choose_passphrase(text: &str, confirm_text: &str) -> Result<SecretString, std::io::Error> {
+    loop {
+        let key = SecretString::new(rpassword::prompt_password(text)?);
+        if key.is_empty() {
+            println!("Passphrase can't be empty.");
+            continue;
+        }
+        let estimate = strength::estimate(key.expose());
+        println!("Strength: {} (about {:.0} bits)", estimate.strength.label(), estimate.bits);
+        if estimate.is_weak() && !ask("This passphrase is easy to guess. Use it anyway? [y/N] ")? {
+            continue;
+        }
+        if SecretString::new(rpassword::prompt_password(confirm_text)?) != key {
+            println!("Passphrases do not match, try again.");
+            continue;
+        }
+        return Ok(key);
+    }
+}
+
+fn create(path: &Path, key: &SecretString, kdf_iter: Option<u32>) -> Result<Database, Box<dyn Error>> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    Ok(Database::create(path, key, kdf_iter)?)
+}
+
+pub fn prompt(text: &str) -> Result<String, std::io::Error> {
+    print!("{}", text);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim().to_owned())
+}
+
+pub fn ask(question: &str) -> Result<bool, std::io::Error> {
+    Ok(prompt(question)?.eq_ignore_ascii_case("y"))
+}
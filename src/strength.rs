@@ -0,0 +1,98 @@
+// a small offline estimator, it's meant to catch the obviously bad passwords and not to be a cracker model
+
+const COMMON_PASSWORDS: &[&str] = &[
+    "password", "123456", "12345678", "123456789", "1234567890", "qwerty", "qwertyuiop",
+    "abc123", "111111", "123123", "letmein", "welcome", "admin", "iloveyou", "monkey",
+    "dragon", "sunshine", "princess", "football", "baseball", "master", "shadow", "passw0rd",
+    "trustno1", "superman", "starwars", "login", "secret", "changeme", "hello"
+];
+
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+pub enum Strength {
+    VeryWeak,
+    Weak,
+    Fair,
+    Strong,
+    VeryStrong
+}
+
+impl Strength {
+
+    pub fn from_bits(bits: f64) -> Strength {
+        if bits < 28.0 {
+            Strength::VeryWeak
+        }else if bits < 36.0 {
+            Strength::Weak
+        }else if bits < 60.0 {
+            Strength::Fair
+        }else if bits < 80.0 {
+            Strength::Strong
+        }else {
+            Strength::VeryStrong
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Strength::VeryWeak => "very weak",
+            Strength::Weak => "weak",
+            Strength::Fair => "fair",
+            Strength::Strong => "strong",
+            Strength::VeryStrong => "very strong"
+        }
+    }
+
+}
+
+pub struct Estimate {
+    pub bits: f64,
+    pub strength: Strength
+}
+
+impl Estimate {
+
+    pub fn is_weak(&self) -> bool {
+        self.strength < Strength::Fair
+    }
+
+}
+
+pub fn estimate(password: &str) -> Estimate {
+    let bits = entropy_bits(password);
+    Estimate { bits, strength: Strength::from_bits(bits) }
+}
+
+fn entropy_bits(password: &str) -> f64 {
+    let lower = password.to_lowercase();
+    if COMMON_PASSWORDS.iter().any(|common| lower == *common || lower.trim_end_matches(|c: char| c.is_ascii_digit() || c == '!') == *common) {
+        return 5.0;
+    }
+
+    let mut pool = 0;
+    if password.chars().any(|c| c.is_ascii_lowercase()) { pool += 26; }
+    if password.chars().any(|c| c.is_ascii_uppercase()) { pool += 26; }
+    if password.chars().any(|c| c.is_ascii_digit()) { pool += 10; }
+    if password.chars().any(|c| c.is_ascii_punctuation() || c == ' ') { pool += 33; }
+    if !password.is_ascii() { pool += 100; }
+    if pool == 0 {
+        return 0.0;
+    }
+
+    // a character that repeats the previous one or continues a run like "abc" or "321"
+    // is nearly free for an attacker, so it only counts as a single bit
+    let per_char = (pool as f64).log2();
+    let mut bits = 0.0;
+    let mut previous: Option<char> = None;
+    for c in password.chars() {
+        let predictable = match previous {
+            Some(p) => {
+                let distance = c as i64 - p as i64;
+                (-1..=1).contains(&distance)
+            }
+            None => false
+        };
+        bits += if predictable { 1.0 } else { per_char };
+        previous = Some(c);
+    }
+    bits
+}